@@ -0,0 +1,47 @@
+//! Decoding of config content stored in a legacy charset, e.g. GBK or Shift_JIS
+//!
+//! `NACOS_CHARSET` names the charset by its WHATWG label. The content is decoded wherever the
+//! raw bytes are at hand: the body of the HTTP protocol and `GZIP(...)` payloads. Over gRPC
+//! Nacos already sends text, which is taken as is.
+
+use crate::NacosError;
+
+/// Whether `charset` is UTF-8, which is also what an unset charset means
+pub(crate) fn is_utf8(charset: Option<&str>) -> bool {
+    charset.is_none_or(|label| matches!(label.trim().to_ascii_lowercase().as_str(), "" | "utf-8" | "utf8"))
+}
+
+/// Decode `bytes` in `charset`, UTF-8 when it is `None`
+///
+/// With `lossy`, invalid UTF-8 is replaced with U+FFFD instead of failing.
+pub(crate) fn decode(bytes: Vec<u8>, charset: Option<&str>, lossy: bool) -> Result<String, NacosError> {
+    match charset {
+        Some(label) if !is_utf8(charset) => decode_legacy(&bytes, label.trim()),
+        _ if lossy => Ok(from_utf8_lossy(bytes)),
+        _ => Ok(String::from_utf8(bytes)?),
+    }
+}
+
+/// The bytes as UTF-8, replacing invalid sequences and warning when there are any
+fn from_utf8_lossy(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes).unwrap_or_else(|e| {
+        let _error = e.utf8_error();
+        warn!(error = %_error, "Config content is not valid UTF-8, replacing the invalid bytes");
+        String::from_utf8_lossy(e.as_bytes()).into_owned()
+    })
+}
+
+#[cfg(feature = "charset")]
+fn decode_legacy(bytes: &[u8], label: &str) -> Result<String, NacosError> {
+    let encoding = encoding_rs::Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| NacosError::EncodingError(format!("Unknown charset: {}", label), None))?;
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .map(|content| content.into_owned())
+        .ok_or_else(|| NacosError::EncodingError(format!("Config content is not valid {}", encoding.name()), None))
+}
+
+#[cfg(not(feature = "charset"))]
+fn decode_legacy(_bytes: &[u8], label: &str) -> Result<String, NacosError> {
+    Err(NacosError::EncodingError(format!("Decoding {} content requires the `charset` feature", label), None))
+}
@@ -0,0 +1,37 @@
+//! Transparent decompression of `GZIP(...)` wrapped config content
+//!
+//! Large configs can be stored gzip-compressed and base64 encoded inside `GZIP(...)` to stay
+//! under the Nacos content size limit.
+
+use crate::NacosError;
+
+/// The decompressed content of a `GZIP(...)` wrapper, `None` when the content is not wrapped
+///
+/// The decompressed bytes are decoded in `charset`, UTF-8 when it is `None`, see
+/// `charset::decode` for `lossy`.
+pub(crate) fn decompress(content: &str, charset: Option<&str>, lossy: bool) -> Result<Option<String>, NacosError> {
+    let Some(encoded) = content.trim().strip_prefix("GZIP(").and_then(|rest| rest.strip_suffix(')')) else {
+        return Ok(None);
+    };
+    gunzip(encoded.trim(), charset, lossy).map(Some)
+}
+
+#[cfg(feature = "gzip")]
+fn gunzip(encoded: &str, charset: Option<&str>, lossy: bool) -> Result<String, NacosError> {
+    use base64::Engine;
+    use std::io::Read;
+
+    let compressed = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| NacosError::Base64DecodeError(format!("Failed to decode base64 of GZIP(...) config: {}", e), Some(e.into())))?;
+    let mut content = Vec::new();
+    flate2::read::GzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut content)
+        .map_err(|e| NacosError::ConfigParseError(format!("Failed to decompress GZIP(...) config: {}", e), Some(e.into())))?;
+    crate::charset::decode(content, charset, lossy)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip(_encoded: &str, _charset: Option<&str>, _lossy: bool) -> Result<String, NacosError> {
+    Err(NacosError::ConfigParseError("Decompressing GZIP(...) configs requires the `gzip` feature".to_string(), None))
+}
@@ -0,0 +1,142 @@
+//! Structured differences between two JSON configs, e.g. the running one and a candidate
+
+use crate::{parse_json, redact, NacosError};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// A field that differs between two configs, keyed by its dotted path, e.g. `database.port`
+///
+/// Array elements are addressed by index, e.g. `hosts[1]`. `Display` masks the values of
+/// secret keys like `password` anywhere in the path or inside the value, so changes can be
+/// logged.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ConfigChange {
+    /// The field only exists in the new config
+    Added { path: String, value: Value },
+    /// The field only exists in the old config
+    Removed { path: String, value: Value },
+    /// The field has a different value, or a different type, e.g. a string became a number
+    Changed { path: String, old: Value, new: Value },
+}
+
+impl ConfigChange {
+    /// Dotted path of the field, empty for the root
+    pub fn path(&self) -> &str {
+        match self {
+            ConfigChange::Added { path, .. } | ConfigChange::Removed { path, .. } | ConfigChange::Changed { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Objects and arrays are masked by their own keys, e.g. `+ database: {"password":"***"}`
+        let shown = |value: &Value| {
+            if redact::is_secret_path(self.path()) {
+                "***".to_string()
+            } else {
+                let text = value.to_string();
+                redact::redact(&text, 0..text.len())
+            }
+        };
+        match self {
+            ConfigChange::Added { path, value } => write!(f, "+ {}: {}", path, shown(value)),
+            ConfigChange::Removed { path, value } => write!(f, "- {}: {}", path, shown(value)),
+            ConfigChange::Changed { path, old, new } => write!(f, "~ {}: {} -> {}", path, shown(old), shown(new)),
+        }
+    }
+}
+
+/// Parse both configs as JSON and list every added, removed and changed field, ordered by path
+pub fn diff_configs(old: &str, new: &str) -> Result<Vec<ConfigChange>, NacosError> {
+    let (old, new): (Value, Value) = (parse_json(old)?, parse_json(new)?);
+    let mut changes = Vec::new();
+    diff(&old, &new, "", &mut changes);
+    Ok(changes)
+}
+
+fn diff(old: &Value, new: &Value, path: &str, changes: &mut Vec<ConfigChange>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys().filter(|key| !old.contains_key(*key))).collect();
+            keys.sort();
+            for key in keys {
+                let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match (old.get(key), new.get(key)) {
+                    (Some(old), Some(new)) => diff(old, new, &child, changes),
+                    (Some(old), None) => changes.push(ConfigChange::Removed { path: child, value: old.clone() }),
+                    (None, Some(new)) => changes.push(ConfigChange::Added { path: child, value: new.clone() }),
+                    (None, None) => {}
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for index in 0..old.len().max(new.len()) {
+                let child = format!("{}[{}]", path, index);
+                match (old.get(index), new.get(index)) {
+                    (Some(old), Some(new)) => diff(old, new, &child, changes),
+                    (Some(old), None) => changes.push(ConfigChange::Removed { path: child, value: old.clone() }),
+                    (None, Some(new)) => changes.push(ConfigChange::Added { path: child, value: new.clone() }),
+                    (None, None) => {}
+                }
+            }
+        }
+        (old, new) if old != new => changes.push(ConfigChange::Changed {
+            path: path.to_string(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn lists_added_removed_and_changed_fields_by_path() {
+        let old = r#"{"name": "app", "port": 80, "hosts": ["a", "b"], "old": true, "db": {"host": "x"}}"#;
+        let new = r#"{"name": "app", "port": "80", "hosts": ["a", "c", "d"], "db": {"host": "y", "pool": 5}}"#;
+        assert_eq!(
+            diff_configs(old, new).unwrap(),
+            vec![
+                ConfigChange::Changed { path: "db.host".to_string(), old: json!("x"), new: json!("y") },
+                ConfigChange::Added { path: "db.pool".to_string(), value: json!(5) },
+                ConfigChange::Changed { path: "hosts[1]".to_string(), old: json!("b"), new: json!("c") },
+                ConfigChange::Added { path: "hosts[2]".to_string(), value: json!("d") },
+                ConfigChange::Removed { path: "old".to_string(), value: json!(true) },
+                ConfigChange::Changed { path: "port".to_string(), old: json!(80), new: json!("80") },
+            ]
+        );
+    }
+
+    #[test]
+    fn identical_configs_have_no_changes() {
+        assert!(diff_configs(r#"{"a": [1, {"b": 2}]}"#, r#"{"a": [1, {"b": 2}]}"#).unwrap().is_empty());
+        assert!(diff_configs("{", "{}").is_err());
+    }
+
+    #[test]
+    fn display_masks_secret_paths() {
+        let change = ConfigChange::Changed { path: "db.password".to_string(), old: json!("a"), new: json!("b") };
+        assert_eq!(change.to_string(), "~ db.password: *** -> ***");
+        let change = ConfigChange::Added { path: "tokens[0]".to_string(), value: json!("abc") };
+        assert_eq!(change.to_string(), "+ tokens[0]: ***");
+        let change = ConfigChange::Changed { path: "port".to_string(), old: json!(1), new: json!(2) };
+        assert_eq!(change.to_string(), "~ port: 1 -> 2");
+    }
+
+    #[test]
+    fn display_masks_secrets_nested_in_added_objects() {
+        let change = ConfigChange::Added { path: "database".to_string(), value: json!({"host": "db", "password": "hunter2"}) };
+        let shown = change.to_string();
+        assert!(!shown.contains("hunter2"), "{}", shown);
+        assert_eq!(shown, r#"+ database: {"host":"db","password":"***"}"#);
+        let change = ConfigChange::Removed { path: "users".to_string(), value: json!([{"name": "a", "apiKey": "k1"}]) };
+        assert_eq!(change.to_string(), r#"- users: [{"apiKey":"***","name":"a"}]"#);
+    }
+}
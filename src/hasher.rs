@@ -0,0 +1,74 @@
+//! Pluggable hashing of the content for the digest check, e.g. a FIPS-approved implementation
+
+use md5::{Digest, Md5};
+use serde::{Serialize, Serializer};
+use sha2::Sha256;
+use std::{fmt, sync::Arc};
+
+/// A hash of the config content, compared with the `md5` field of the Nacos response
+pub trait ContentHasher: Send + Sync {
+    /// Name of the algorithm, shown in digest mismatch errors
+    fn name(&self) -> &str;
+
+    /// Hex digest of `content`, compared with the response ignoring case
+    fn hex_digest(&self, content: &[u8]) -> String;
+}
+
+/// MD5, what Nacos reports by default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Md5Hasher;
+
+impl ContentHasher for Md5Hasher {
+    fn name(&self) -> &str {
+        "Md5"
+    }
+
+    fn hex_digest(&self, content: &[u8]) -> String {
+        format!("{:x}", Md5::digest(content))
+    }
+}
+
+/// SHA-256, for servers that report a SHA-256 digest in place of the MD5
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha256Hasher;
+
+impl ContentHasher for Sha256Hasher {
+    fn name(&self) -> &str {
+        "Sha256"
+    }
+
+    fn hex_digest(&self, content: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(content))
+    }
+}
+
+/// A [`ContentHasher`] set on a [`crate::NacosConfig`], shared by its clones
+///
+/// Two values are only equal when they share the same hasher. `Debug` and `Serialize` show
+/// the name of the algorithm.
+#[derive(Clone)]
+pub struct CustomHasher(pub Arc<dyn ContentHasher>);
+
+impl CustomHasher {
+    pub fn new(hasher: impl ContentHasher + 'static) -> Self {
+        CustomHasher(Arc::new(hasher))
+    }
+}
+
+impl PartialEq for CustomHasher {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for CustomHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CustomHasher").field(&self.0.name()).finish()
+    }
+}
+
+impl Serialize for CustomHasher {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.name())
+    }
+}
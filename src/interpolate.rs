@@ -0,0 +1,87 @@
+//! Spring-style `${VAR}` and `${VAR:-default}` substitution from the process environment
+
+use crate::NacosError;
+use std::env;
+
+/// Replace every `${VAR}` and `${VAR:-default}` in `content`, an unterminated `${` is kept as is
+///
+/// In `json` content the values are escaped as JSON string characters, so a quote or backslash
+/// in a value cannot break out of the string it is placed in.
+pub(crate) fn interpolate(content: &str, json: bool) -> Result<String, NacosError> {
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let value = resolve(&rest[start + 2..start + 2 + len])?;
+        if json {
+            // Quoted JSON string without the quotes
+            let quoted = serde_json::to_string(&value)?;
+            out.push_str(&quoted[1..quoted.len() - 1]);
+        } else {
+            out.push_str(&value);
+        }
+        rest = &rest[start + 2 + len + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn resolve(placeholder: &str) -> Result<String, NacosError> {
+    let (name, default) = match placeholder.split_once(":-") {
+        Some((name, default)) => (name.trim(), Some(default)),
+        None => (placeholder.trim(), None),
+    };
+    match (env::var(name), default) {
+        (Ok(value), _) => Ok(value),
+        (Err(_), Some(default)) => Ok(default.to_string()),
+        (Err(e), None) => Err(NacosError::ConfigParseError(
+            format!("Environment variable referenced in config is not set and has no default: {}", name),
+            Some(e.into()),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variables_and_defaults_are_substituted() {
+        env::set_var("SOUTHEAST_INTERPOLATE_TEST_HOST", "db.internal");
+        env::remove_var("SOUTHEAST_INTERPOLATE_TEST_UNSET");
+        let content = "host=${SOUTHEAST_INTERPOLATE_TEST_HOST}\nport=${SOUTHEAST_INTERPOLATE_TEST_UNSET:-5432}\nempty=${ SOUTHEAST_INTERPOLATE_TEST_UNSET :-}";
+        assert_eq!(interpolate(content, false).unwrap(), "host=db.internal\nport=5432\nempty=");
+    }
+
+    #[test]
+    fn unset_variables_without_default_are_errors() {
+        env::remove_var("SOUTHEAST_INTERPOLATE_TEST_MISSING");
+        let err = interpolate("a=${SOUTHEAST_INTERPOLATE_TEST_MISSING}", false).unwrap_err();
+        assert!(matches!(err, NacosError::ConfigParseError(..)), "{:?}", err);
+        assert!(err.to_string().contains("SOUTHEAST_INTERPOLATE_TEST_MISSING"), "{}", err);
+    }
+
+    #[test]
+    fn unterminated_placeholders_are_kept() {
+        assert_eq!(interpolate("price=$5 and ${OPEN", false).unwrap(), "price=$5 and ${OPEN");
+    }
+
+    #[test]
+    fn json_values_are_escaped() {
+        env::set_var("SOUTHEAST_INTERPOLATE_TEST_QUOTED", "a \"quoted\" \\ value\nnext");
+        let content = r#"{"name": "${SOUTHEAST_INTERPOLATE_TEST_QUOTED}", "port": ${SOUTHEAST_INTERPOLATE_TEST_PORT:-5432}}"#;
+        let interpolated = interpolate(content, true).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&interpolated).unwrap();
+        assert_eq!(value["name"], "a \"quoted\" \\ value\nnext");
+        assert_eq!(value["port"], 5432);
+    }
+
+    #[test]
+    fn other_content_is_not_escaped() {
+        env::set_var("SOUTHEAST_INTERPOLATE_TEST_RAW", "a \"b\"");
+        assert_eq!(interpolate("v=${SOUTHEAST_INTERPOLATE_TEST_RAW}", false).unwrap(), "v=a \"b\"");
+    }
+}
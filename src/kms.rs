@@ -0,0 +1,345 @@
+//! AWS KMS encryption and decryption of `ENC(...)` passwords
+
+use crate::{DecryptedSecret, NacosConfig, NacosError, SecretDecryptor};
+use aws_config::{
+    meta::region::RegionProviderChain, retry::RetryConfig, timeout::TimeoutConfig, BehaviorVersion, Region, SdkConfig,
+};
+use aws_sdk_kms as kms;
+use base64::Engine;
+use kms::{
+    error::{DisplayErrorContext, ProvideErrorMetadata},
+    primitives::Blob,
+};
+use serde::Serialize;
+use std::{collections::HashMap, env, error::Error, fmt, sync::Arc, time::Duration};
+
+/// Timeout and retries of the KMS client, tuned independently of fetching from Nacos
+///
+/// Unset fields are read from `KMS_TIMEOUT_MS` and `KMS_MAX_RETRIES`, and keep the AWS SDK's
+/// defaults when those are not set either.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct KmsConfig {
+    /// Maximum time for a KMS call including its retries
+    pub timeout: Option<Duration>,
+    /// Retries after the first attempt, e.g. when KMS throttles, `0` disables them
+    pub max_retries: Option<u32>,
+}
+
+impl KmsConfig {
+    /// Fill the unset fields from `KMS_TIMEOUT_MS` and `KMS_MAX_RETRIES`
+    fn or_env(&self) -> Result<KmsConfig, NacosError> {
+        let timeout = match self.timeout {
+            Some(timeout) => Some(timeout),
+            None => crate::optional_env_ms("KMS_TIMEOUT_MS")?,
+        };
+        let max_retries = match (self.max_retries, env::var("KMS_MAX_RETRIES")) {
+            (Some(max_retries), _) => Some(max_retries),
+            (None, Ok(value)) => Some(value.parse::<u32>().map_err(|e| {
+                NacosError::EnvVarError(format!("KMS_MAX_RETRIES is not a valid number of retries: {}", value), Some(e.into()))
+            })?),
+            (None, Err(_)) => None,
+        };
+        Ok(KmsConfig { timeout, max_retries })
+    }
+}
+
+/// A prebuilt KMS client set on a [`NacosConfig`], shared by its clones
+///
+/// Two values are only equal when they share the same client.
+#[derive(Clone)]
+pub struct SharedKmsClient(pub Arc<kms::Client>);
+
+impl SharedKmsClient {
+    pub fn new(client: kms::Client) -> Self {
+        SharedKmsClient(Arc::new(client))
+    }
+}
+
+impl PartialEq for SharedKmsClient {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for SharedKmsClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedKmsClient(..)")
+    }
+}
+
+/// Decrypts `ENC(...)` passwords with AWS KMS, using the key in `KMS_KEY_ID` when set
+#[derive(Debug, Clone, Default)]
+pub struct KmsDecryptor {
+    /// AWS region of the key, see [`NacosConfig::kms_region`]
+    pub region: Option<String>,
+    /// Endpoint URL overriding real AWS, see [`NacosConfig::kms_endpoint`]
+    pub endpoint: Option<String>,
+    /// Encryption context, see [`NacosConfig::kms_encryption_context`]
+    pub encryption_context: HashMap<String, String>,
+    /// Timeout and retries of the client that is built, see [`KmsConfig`]
+    pub config: KmsConfig,
+    /// Prebuilt client to use instead of building one, `region`, `endpoint` and `config` are then ignored
+    pub client: Option<kms::Client>,
+}
+
+impl KmsDecryptor {
+    /// Use the KMS settings of `cfg`, including its prebuilt client
+    pub fn from_config(cfg: &NacosConfig) -> Self {
+        KmsDecryptor {
+            region: cfg.kms_region.clone(),
+            endpoint: cfg.kms_endpoint.clone(),
+            encryption_context: cfg.kms_encryption_context.clone(),
+            config: cfg.kms.clone(),
+            client: cfg.kms_client.as_ref().map(|client| client.0.as_ref().clone()),
+        }
+    }
+
+    /// Use an existing KMS client, e.g. one sharing the application's retry and credential settings
+    pub fn with_client(client: kms::Client) -> Self {
+        KmsDecryptor {
+            client: Some(client),
+            ..KmsDecryptor::default()
+        }
+    }
+
+    /// Build the client from the application's shared AWS config instead of loading a new one
+    pub fn from_sdk_config(config: &SdkConfig) -> Self {
+        KmsDecryptor::with_client(kms::Client::new(config))
+    }
+
+    /// Encrypt `plaintext` with the `KMS_KEY_ID` key, returning the base64 encoded ciphertext
+    pub(crate) async fn encrypt(&self, plaintext: &str) -> Result<String, NacosError> {
+        let key = key_id()?;
+        let context = self.encryption_context()?;
+        let kms_client = self.get_kms_client(&self.config.or_env()?).await;
+        let resp = kms_client
+            .encrypt()
+            .key_id(key)
+            .plaintext(Blob::new(plaintext.as_bytes()))
+            .set_encryption_context(context)
+            .send()
+            .await
+            .map_err(|e| kms_error("Failed to encrypt plaintext with kms", e))?;
+
+        let blob = resp.ciphertext_blob
+            .ok_or_else(|| NacosError::KmsError("Failed to get ciphertext from kms's response".to_string(), None))?;
+        Ok(base64::engine::general_purpose::STANDARD.encode(blob.as_ref()))
+    }
+
+    /// The configured encryption context, or parsed from `KMS_ENCRYPTION_CONTEXT` (`k1=v1,k2=v2`)
+    fn encryption_context(&self) -> Result<Option<HashMap<String, String>>, NacosError> {
+        if !self.encryption_context.is_empty() {
+            return Ok(Some(self.encryption_context.clone()));
+        }
+        match env::var("KMS_ENCRYPTION_CONTEXT") {
+            Ok(value) if !value.trim().is_empty() => parse_encryption_context(&value).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Get KMS client, the prebuilt `client` when there is one
+    ///
+    /// Credentials come from the default AWS provider chain: environment variables, the shared
+    /// config and credentials files (including SSO and `credential_process`), web identity
+    /// tokens (EKS IRSA), ECS task roles and EC2 instance profiles, tried in that order.
+    ///
+    /// An explicit `region` takes precedence, otherwise the default AWS region provider chain
+    /// is used, falling back to `KMS_REGION` and then `ap-southeast-1`. The endpoint is only
+    /// overridden when `endpoint` or `AWS_KMS_ENDPOINT` is set, e.g. for LocalStack.
+    ///
+    /// `kms_config` replaces the SDK's timeout and retry defaults where it is set.
+    async fn get_kms_client(&self, kms_config: &KmsConfig) -> kms::Client {
+        if let Some(client) = &self.client {
+            return client.clone();
+        }
+        let fallback = env::var("KMS_REGION").unwrap_or_else(|_| "ap-southeast-1".to_string());
+        let region_provider = match self.region.as_deref() {
+            Some(region) => RegionProviderChain::first_try(Region::new(region.to_string())),
+            None => RegionProviderChain::default_provider(),
+        }
+        .or_else(Region::new(fallback));
+        let config = aws_config::defaults(BehaviorVersion::latest()).region(region_provider).load().await;
+        let mut builder = kms::config::Builder::from(&config);
+        if let Some(endpoint) = self.endpoint.clone().or_else(|| env::var("AWS_KMS_ENDPOINT").ok()) {
+            builder = builder.endpoint_url(endpoint);
+        }
+        if let Some(timeout) = kms_config.timeout {
+            builder = builder.timeout_config(TimeoutConfig::builder().operation_timeout(timeout).build());
+        }
+        if let Some(max_retries) = kms_config.max_retries {
+            builder = builder.retry_config(RetryConfig::standard().with_max_attempts(max_retries.saturating_add(1)));
+        }
+        kms::Client::from_conf(builder.build())
+    }
+}
+
+#[async_trait::async_trait]
+impl SecretDecryptor for KmsDecryptor {
+    /// Decrypt the base64 encoded ciphertext of an `ENC(...)` value
+    ///
+    /// Without `KMS_KEY_ID` AWS takes the key from the ciphertext's metadata, which works for
+    /// symmetric keys. When set, KMS rejects ciphertext that was encrypted with another key.
+    async fn decrypt(&self, ciphertext: &str) -> Result<String, NacosError> {
+        Ok(self.decrypt_secret(ciphertext).await?.value)
+    }
+
+    /// Decrypt like [`KmsDecryptor::decrypt`], reporting the ARN of the key KMS used
+    async fn decrypt_secret(&self, ciphertext: &str) -> Result<DecryptedSecret, NacosError> {
+        let key = env::var("KMS_KEY_ID").ok();
+        let context = self.encryption_context()?;
+        let blob = get_blob(ciphertext)?;
+        let kms_client = self.get_kms_client(&self.config.or_env()?).await;
+        let (value, key_id) = decrypt_blob(&kms_client, key, blob, context).await?;
+        Ok(DecryptedSecret {
+            value,
+            via_kms: true,
+            key_id,
+            cached: false,
+        })
+    }
+}
+
+fn key_id() -> Result<String, NacosError> {
+    env::var("KMS_KEY_ID").map_err(|e| NacosError::EnvVarError("KMS_KEY_ID not set".to_string(), Some(e.into())))
+}
+
+fn parse_encryption_context(value: &str) -> Result<HashMap<String, String>, NacosError> {
+    value
+        .split(',')
+        .map(|pair| {
+            let (key, val) = pair
+                .split_once('=')
+                .filter(|(key, _)| !key.trim().is_empty())
+                .ok_or_else(|| NacosError::EnvVarError(format!("KMS_ENCRYPTION_CONTEXT is malformed, expected k1=v1,k2=v2: {}", value), None))?;
+            Ok((key.trim().to_string(), val.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Decode the ciphertext, accepting URL-safe and unpadded base64 from other encoders
+fn get_blob(raw_password: &str) -> Result<Blob, NacosError> {
+    use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+    let standard_error = match STANDARD.decode(raw_password) {
+        Ok(raw) => return Ok(Blob::new(raw)),
+        Err(e) => e,
+    };
+    for engine in [URL_SAFE, STANDARD_NO_PAD, URL_SAFE_NO_PAD] {
+        if let Ok(raw) = engine.decode(raw_password) {
+            return Ok(Blob::new(raw));
+        }
+    }
+    let msg = format!("Failed to decode base64, tried standard, url-safe, standard without padding and url-safe without padding: {}: {}", raw_password, standard_error);
+    Err(NacosError::Base64DecodeError(msg, Some(standard_error.into())))
+}
+
+async fn decrypt_blob(
+    client: &kms::Client,
+    key: Option<String>,
+    blob: Blob,
+    context: Option<HashMap<String, String>>,
+) -> Result<(String, Option<String>), NacosError> {
+    let resp = client
+        .decrypt()
+        .set_key_id(key)
+        .ciphertext_blob(blob)
+        .set_encryption_context(context)
+        .send()
+        .await
+        .map_err(|e| kms_error("Failed to decrypt blob from kms", e))?;
+    
+    let inner = resp.plaintext
+        .ok_or_else(|| NacosError::KmsError("Failed to get plaintext from kms's response".to_string(), None))?;
+    
+    let bytes = inner.as_ref();
+    Ok((String::from_utf8(bytes.to_vec())?, resp.key_id))
+}
+
+/// Map a failed KMS call to the variant for its error code, the code is part of the message
+fn kms_error<E>(action: &str, e: E) -> NacosError
+where
+    E: ProvideErrorMetadata + Error + Send + Sync + 'static,
+{
+    let code = e.code().unwrap_or("Unknown").to_string();
+    // The plain `Display` of the SDK's errors only says "service error", the context has the details
+    let msg = format!("{}: {}: {}", action, code, DisplayErrorContext(&e));
+    let source = Some(e.into());
+    match code.as_str() {
+        "AccessDeniedException" | "UnrecognizedClientException" => NacosError::KmsAccessDenied(msg, source),
+        "KeyUnavailableException" | "DisabledException" | "KMSInvalidStateException" | "NotFoundException" => {
+            NacosError::KmsKeyUnavailable(msg, source)
+        }
+        "ThrottlingException" => NacosError::KmsThrottled(msg, source),
+        _ => NacosError::KmsError(msg, source),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kms::{error::ErrorMetadata, operation::decrypt::DecryptError};
+
+    fn service_error(code: &str) -> DecryptError {
+        DecryptError::generic(ErrorMetadata::builder().code(code).message("from kms").build())
+    }
+
+    #[test]
+    fn kms_error_codes_map_to_variants() {
+        let kind = |code: &str| kms_error("Failed to decrypt blob from kms", service_error(code)).kind();
+        let expected = |code: &str| match code {
+            "AccessDeniedException" | "UnrecognizedClientException" => NacosError::KmsAccessDenied(String::new(), None).kind(),
+            "ThrottlingException" => NacosError::KmsThrottled(String::new(), None).kind(),
+            "KeyUnavailableException" | "DisabledException" | "KMSInvalidStateException" | "NotFoundException" => {
+                NacosError::KmsKeyUnavailable(String::new(), None).kind()
+            }
+            _ => NacosError::KmsError(String::new(), None).kind(),
+        };
+        for code in [
+            "AccessDeniedException",
+            "UnrecognizedClientException",
+            "KeyUnavailableException",
+            "DisabledException",
+            "KMSInvalidStateException",
+            "NotFoundException",
+            "ThrottlingException",
+            "InvalidCiphertextException",
+        ] {
+            assert_eq!(kind(code), expected(code), "{}", code);
+        }
+        assert!(kms_error("x", service_error("ThrottlingException")).is_throttled());
+        assert!(!kms_error("x", service_error("AccessDeniedException")).is_throttled());
+    }
+
+    #[test]
+    fn kms_error_messages_carry_the_code_and_details() {
+        let err = kms_error("Failed to decrypt blob from kms", service_error("InvalidCiphertextException"));
+        assert!(matches!(err, NacosError::KmsError(..)), "{:?}", err);
+        let msg = err.to_string();
+        assert!(msg.contains("Failed to decrypt blob from kms: InvalidCiphertextException"), "{}", msg);
+        assert!(msg.contains("from kms"), "{}", msg);
+    }
+
+    #[test]
+    fn errors_without_a_code_are_unknown() {
+        let err = kms_error("x", DecryptError::generic(ErrorMetadata::builder().build()));
+        assert!(matches!(err, NacosError::KmsError(..)), "{:?}", err);
+        assert!(err.to_string().contains("x: Unknown"), "{}", err);
+    }
+
+    #[test]
+    fn get_blob_accepts_every_base64_alphabet() {
+        // 0xfb 0xff encodes to "+/8=" in the standard alphabet and "-_8=" in the url-safe one.
+        for encoded in ["+/8=", "-_8=", "+/8", "-_8"] {
+            let blob = get_blob(encoded).unwrap_or_else(|e| panic!("{}: {}", encoded, e));
+            assert_eq!(blob.as_ref(), &[0xfb, 0xff], "{}", encoded);
+        }
+    }
+
+    #[test]
+    fn get_blob_reports_the_alphabets_it_tried() {
+        let err = get_blob("not base64!").unwrap_err();
+        assert!(matches!(err, NacosError::Base64DecodeError(..)), "{:?}", err);
+        let msg = err.to_string();
+        assert!(msg.contains("tried standard, url-safe"), "{}", msg);
+        assert!(msg.contains("not base64!"), "{}", msg);
+    }
+}
@@ -0,0 +1,32 @@
+//! Config read from a local file instead of Nacos, for offline development
+//!
+//! Set `NACOS_LOCAL_FILE` to the path of the file. No other `NACOS_*` variables are read and
+//! neither Nacos, KMS nor the MD5 check are involved.
+
+use crate::NacosError;
+use std::{env, path::Path};
+
+/// A config file standing in for Nacos
+pub(crate) struct LocalFile {
+    pub(crate) content: String,
+    /// Parser for the content, from the file extension and `json` for any other
+    pub(crate) format: &'static str,
+}
+
+/// The file in `NACOS_LOCAL_FILE`, `None` when it is not set
+pub(crate) fn file() -> Result<Option<LocalFile>, NacosError> {
+    let Ok(path) = env::var("NACOS_LOCAL_FILE") else {
+        return Ok(None);
+    };
+    warn!(path = %path, "NACOS_LOCAL_FILE is set, reading the config from a local file instead of nacos");
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| NacosError::EnvVarError(format!("Failed to read NACOS_LOCAL_FILE: {}: {}", path, e), Some(e.into())))?;
+    let extension = Path::new(&path).extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    let format = match extension.to_ascii_lowercase().as_str() {
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "properties" => "properties",
+        _ => "json",
+    };
+    Ok(Some(LocalFile { content, format }))
+}
@@ -0,0 +1,19 @@
+//! Fetch latency and outcome metrics through the `metrics` facade when the `metrics` feature is enabled
+//!
+//! Without the feature nothing is recorded.
+
+use crate::NacosError;
+use std::time::Duration;
+
+/// Record how long a config fetch took and whether it succeeded
+#[cfg(feature = "metrics")]
+pub(crate) fn record_fetch<T>(elapsed: Duration, result: &Result<T, NacosError>) {
+    ::metrics::histogram!("nacos_config_fetch_duration_seconds").record(elapsed.as_secs_f64());
+    match result {
+        Ok(_) => ::metrics::counter!("nacos_config_fetch_total", "result" => "success").increment(1),
+        Err(e) => ::metrics::counter!("nacos_config_fetch_total", "result" => "failure", "error" => e.kind()).increment(1),
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn record_fetch<T>(_elapsed: Duration, _result: &Result<T, NacosError>) {}
@@ -0,0 +1,18 @@
+//! Config content injected from the environment for downstream test suites, no Nacos involved
+
+use crate::NacosError;
+use std::env;
+
+/// Content from `NACOS_MOCK_CONTENT` or the file in `NACOS_MOCK_FILE`, `None` when neither is set
+pub(crate) fn content() -> Result<Option<String>, NacosError> {
+    if let Ok(content) = env::var("NACOS_MOCK_CONTENT") {
+        return Ok(Some(content));
+    }
+    let Ok(path) = env::var("NACOS_MOCK_FILE") else {
+        return Ok(None);
+    };
+    debug!(path = %path, "Reading mock config from file");
+    std::fs::read_to_string(&path)
+        .map(Some)
+        .map_err(|e| NacosError::EnvVarError(format!("Failed to read NACOS_MOCK_FILE: {}: {}", path, e), Some(e.into())))
+}
@@ -0,0 +1,497 @@
+//! Calls to the Nacos HTTP OpenAPI for what the SDK does not cover, e.g. listing namespaces
+//!
+//! [`HttpConfigService`] also serves configs over the OpenAPI for networks where only the HTTP
+//! port is reachable and the SDK's gRPC port is blocked.
+
+use crate::{
+    decrypt_password_with_config, normalize_addr, ContentDigest, ContentHasher, Md5Hasher, NacosConfig, NacosError, Sha256Hasher,
+};
+use nacos_sdk::api::{
+    config::{ConfigChangeListener, ConfigResponse, ConfigService},
+    error::{Error as SdkError, Result as SdkResult},
+};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex as StdMutex, OnceLock, PoisonError,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::Mutex;
+
+/// Log in again this long before the token expires, so a request never carries a stale one
+const TOKEN_RENEW_MARGIN: Duration = Duration::from_secs(60);
+
+/// Parameter named by the `InvalidParam` error for content not valid in the configured charset
+pub(crate) const CHARSET_PARAM: &str = "charset";
+
+tokio::task_local! {
+    /// Digest of the bytes nacos sent, set by [`HttpConfigService::get_config`] when it replaced invalid UTF-8
+    static RAW_DIGEST: Cell<Option<String>>;
+}
+
+/// Run a `get_config` and return the digest of the raw bytes when the content no longer matches them
+///
+/// The md5 of the response stays the one nacos reported, so the digest check compares this
+/// digest with it instead of hashing the replaced content.
+pub(crate) async fn with_raw_digest<T>(fetch: impl Future<Output = T>) -> (T, Option<String>) {
+    RAW_DIGEST
+        .scope(Cell::new(None), async {
+            let output = fetch.await;
+            (output, RAW_DIGEST.with(Cell::take))
+        })
+        .await
+}
+
+/// Base URLs of the servers in `cfg.addr` in their configured order, e.g. `http://nacos-server:8848/nacos`
+///
+/// Never empty, `normalize_addr` rejects an address without servers.
+fn base_urls(cfg: &NacosConfig) -> Result<Vec<String>, NacosError> {
+    let scheme = if cfg.tls || crate::is_https(&cfg.addr) { "https" } else { "http" };
+    Ok(normalize_addr(&cfg.addr)?
+        .split(',')
+        .map(|server| format!("{}://{}/nacos", scheme, server))
+        .collect())
+}
+
+/// Whether the server could not be reached or did not answer in time, so another server of the cluster may
+fn is_unanswered(e: &NacosError) -> bool {
+    std::error::Error::source(e)
+        .and_then(|source| source.downcast_ref::<reqwest::Error>())
+        .is_some_and(|e| e.is_connect() || e.is_timeout())
+}
+
+/// Log in with the username and password of `cfg`, `None` for anonymous Nacos
+async fn access_token(client: &reqwest::Client, base: &str, cfg: &NacosConfig) -> Result<Option<AccessToken>, NacosError> {
+    if cfg.username.is_empty() && cfg.password.is_empty() {
+        return Ok(None);
+    }
+    let password = decrypt_password_with_config(&cfg.password, cfg).await?;
+    login(client, base, &cfg.username, &password).await.map(Some)
+}
+
+/// Token returned by nacos's login endpoint and when to log in again
+struct AccessToken {
+    token: String,
+    renew_at: Instant,
+}
+
+async fn login(client: &reqwest::Client, base: &str, username: &str, password: &str) -> Result<AccessToken, NacosError> {
+    let resp: Value = client
+        .post(format!("{}/v1/auth/login", base))
+        .form(&[("username", username), ("password", password)])
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| http_error(format!("Failed to log in to nacos: {}", base), e))?
+        .json()
+        .await
+        .map_err(|e| NacosError::NacosConnectionError(format!("Failed to read nacos's login response: {}", e), Some(e.into())))?;
+    let token = resp["accessToken"]
+        .as_str()
+        .ok_or_else(|| NacosError::NacosConnectionError("Failed to get accessToken from nacos's login response".to_string(), None))?;
+    // Nacos issues tokens for 18000 seconds unless configured otherwise
+    let ttl = Duration::from_secs(resp["tokenTtl"].as_u64().unwrap_or(18000));
+    Ok(AccessToken {
+        token: token.to_string(),
+        renew_at: Instant::now() + ttl.saturating_sub(TOKEN_RENEW_MARGIN),
+    })
+}
+
+/// An `AuthError` when nacos answered 401 or 403, a `NacosConnectionError` otherwise
+fn http_error(msg: String, e: reqwest::Error) -> NacosError {
+    if matches!(e.status(), Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)) {
+        return NacosError::AuthError(format!("{}: nacos rejected the username/password credentials: {}", msg, e), Some(e.into()));
+    }
+    NacosError::NacosConnectionError(format!("{}: {}", msg, e), Some(e.into()))
+}
+
+/// Server, username and configured password a cached token was issued for
+type TokenKey = (String, String, String);
+
+/// Tokens of the [`get_json`] calls, shared by every config with the same server and credentials
+fn token_cache() -> &'static StdMutex<HashMap<TokenKey, AccessToken>> {
+    static TOKENS: OnceLock<StdMutex<HashMap<TokenKey, AccessToken>>> = OnceLock::new();
+    TOKENS.get_or_init(Default::default)
+}
+
+/// The cached token for `cfg`, logging in when there is none or it is due for renewal
+async fn cached_token(client: &reqwest::Client, base: &str, cfg: &NacosConfig) -> Result<Option<String>, NacosError> {
+    if cfg.username.is_empty() && cfg.password.is_empty() {
+        return Ok(None);
+    }
+    let key = (base.to_string(), cfg.username.clone(), cfg.password.clone());
+    if let Some(token) = token_cache().lock().unwrap_or_else(PoisonError::into_inner).get(&key) {
+        if Instant::now() < token.renew_at {
+            return Ok(Some(token.token.clone()));
+        }
+    }
+    let Some(token) = access_token(client, base, cfg).await? else {
+        return Ok(None);
+    };
+    let value = token.token.clone();
+    token_cache().lock().unwrap_or_else(PoisonError::into_inner).insert(key, token);
+    Ok(Some(value))
+}
+
+/// GET `path` from the servers of `cfg`, authenticated when `cfg` has credentials
+///
+/// The servers are tried in turn until one answers. The access token is cached until nacos's
+/// `tokenTtl` runs out, and dropped when nacos rejects it so the next call logs in again.
+async fn get_json(cfg: &NacosConfig, path: &str, query: &[(&str, &str)]) -> Result<Value, NacosError> {
+    let client = crate::tls::http_client(cfg)?;
+    let bases = base_urls(cfg)?;
+    let (last, others) = bases.split_last().expect("nacos address without servers");
+    for base in others {
+        match get_json_from(&client, base, cfg, path, query).await {
+            Err(e) if is_unanswered(&e) => {
+                debug!(base = %base, error = %e, "Nacos server did not answer, trying the next one");
+            }
+            result => return result,
+        }
+    }
+    get_json_from(&client, last, cfg, path, query).await
+}
+
+async fn get_json_from(client: &reqwest::Client, base: &str, cfg: &NacosConfig, path: &str, query: &[(&str, &str)]) -> Result<Value, NacosError> {
+    let mut request = client.get(format!("{}{}", base, path)).query(query);
+    if let Some(token) = cached_token(client, base, cfg).await? {
+        request = request.query(&[("accessToken", token)]);
+    }
+    let result = request.send().await.and_then(|resp| resp.error_for_status());
+    if matches!(&result, Err(e) if matches!(e.status(), Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN))) {
+        let key = (base.to_string(), cfg.username.clone(), cfg.password.clone());
+        token_cache().lock().unwrap_or_else(PoisonError::into_inner).remove(&key);
+    }
+    result
+        .map_err(|e| http_error(format!("Failed to call nacos: {}{}", base, path), e))?
+        .json()
+        .await
+        .map_err(|e| NacosError::NacosConnectionError(format!("Failed to read nacos's response: {}{}: {}", base, path, e), Some(e.into())))
+}
+
+/// Id of the namespace whose display name is `name`
+pub(crate) async fn namespace_id(cfg: &NacosConfig, name: &str) -> Result<String, NacosError> {
+    let resp = get_json(cfg, "/v1/console/namespaces", &[]).await?;
+    let namespaces = resp["data"]
+        .as_array()
+        .ok_or_else(|| NacosError::NacosConfigError("Failed to get namespaces from nacos's response".to_string(), None))?;
+    namespaces
+        .iter()
+        .find(|namespace| namespace["namespaceShowName"].as_str() == Some(name))
+        .and_then(|namespace| namespace["namespace"].as_str())
+        .map(str::to_string)
+        .ok_or_else(|| NacosError::NacosConfigError(format!("No namespace named {} in nacos", name), None))
+}
+
+/// The beta (gray release) variant of a config, `None` when it has none
+///
+/// Queried from the console API, which returns the beta content without checking `betaIps`
+/// against the client's IP.
+pub(crate) async fn beta_config(cfg: &NacosConfig, data_id: &str, group: &str) -> Result<Option<ConfigResponse>, NacosError> {
+    let query = [("beta", "true"), ("dataId", data_id), ("group", group), ("tenant", cfg.namespace.as_str())];
+    let resp = get_json(cfg, "/v1/cs/configs", &query).await?;
+    let data = &resp["data"];
+    let Some(content) = data["content"].as_str() else {
+        return Ok(None);
+    };
+    let field = |name: &str| data[name].as_str().unwrap_or_default().to_string();
+    Ok(Some(ConfigResponse::new(
+        data_id.to_string(),
+        group.to_string(),
+        cfg.namespace.clone(),
+        content.to_string(),
+        field("type"),
+        field("md5"),
+    )))
+}
+
+/// The md5 of a config and when it was last modified, `None` when nacos does not report it
+///
+/// Read from the console's detail view, the config itself does not carry the time.
+pub(crate) async fn modified_time(cfg: &NacosConfig, data_id: &str, group: &str) -> Result<Option<(String, SystemTime)>, NacosError> {
+    let query = [("show", "all"), ("dataId", data_id), ("group", group), ("tenant", cfg.namespace.as_str())];
+    let resp = get_json(cfg, "/v1/cs/configs", &query).await?;
+    let (Some(md5), Some(modified)) = (resp["md5"].as_str(), resp["modifyTime"].as_u64()) else {
+        return Ok(None);
+    };
+    Ok(Some((md5.to_string(), UNIX_EPOCH + Duration::from_millis(modified))))
+}
+
+/// [`ConfigService`] over the Nacos HTTP OpenAPI (`/v1/cs/configs`), selected by [`crate::Protocol::Http`]
+///
+/// Listeners need the SDK's gRPC connection and are rejected, poll for changes instead.
+pub(crate) struct HttpConfigService {
+    client: reqwest::Client,
+    /// Base URLs of every server, tried in turn when one does not answer
+    bases: Vec<String>,
+    /// Index of the server that answered last, the first one tried by the next request
+    current: AtomicUsize,
+    namespace: String,
+    app_name: Option<String>,
+    /// Charset of the response body, UTF-8 when `None`
+    charset: Option<String>,
+    /// Replace invalid UTF-8 in the body instead of failing
+    lossy_utf8: bool,
+    /// Hasher of the digest check, `None` when the digest is not verified
+    hasher: Option<Arc<dyn ContentHasher>>,
+    /// Username and decrypted password, `None` for anonymous Nacos
+    credentials: Option<(String, String)>,
+    token: Mutex<Option<AccessToken>>,
+}
+
+impl HttpConfigService {
+    /// Prepare the service, the password is decrypted once here and nacos is first called on use
+    pub(crate) async fn new(cfg: &NacosConfig, app_name: Option<String>) -> Result<Self, NacosError> {
+        if cfg.access_key.is_some() || cfg.secret_key.is_some() {
+            return Err(NacosError::NacosConnectionError("Access key authentication is not supported over the HTTP protocol".to_string(), None));
+        }
+        let credentials = if cfg.username.is_empty() && cfg.password.is_empty() {
+            None
+        } else {
+            Some((cfg.username.clone(), decrypt_password_with_config(&cfg.password, cfg).await?))
+        };
+        Ok(HttpConfigService {
+            client: crate::tls::http_client(cfg)?,
+            bases: base_urls(cfg)?,
+            current: AtomicUsize::new(0),
+            namespace: cfg.namespace.clone(),
+            app_name,
+            charset: cfg.charset.clone(),
+            lossy_utf8: cfg.lossy_utf8,
+            hasher: cfg.verify_md5.then(|| match (&cfg.content_hasher, cfg.content_digest) {
+                (Some(hasher), _) => hasher.0.clone(),
+                (None, ContentDigest::Md5) => Arc::new(Md5Hasher) as Arc<dyn ContentHasher>,
+                (None, ContentDigest::Sha256) => Arc::new(Sha256Hasher),
+            }),
+            credentials,
+            token: Mutex::new(None),
+        })
+    }
+
+    /// A valid access token, logging in at `base` when there is none yet or it is about to expire
+    async fn token(&self, base: &str) -> Result<Option<String>, NacosError> {
+        let Some((username, password)) = &self.credentials else {
+            return Ok(None);
+        };
+        let mut token = self.token.lock().await;
+        if token.as_ref().is_none_or(|token| Instant::now() >= token.renew_at) {
+            *token = Some(login(&self.client, base, username, password).await?);
+        }
+        Ok(token.as_ref().map(|token| token.token.clone()))
+    }
+
+    /// Send a request to `/v1/cs/configs` with the config's coordinates and the access token
+    ///
+    /// Starts with the server that answered last and moves on to the next one while a server
+    /// cannot be reached. A rejected token is dropped, like in [`get_json`].
+    async fn send(&self, method: reqwest::Method, data_id: &str, group: &str, build: impl Fn(RequestBuilder) -> RequestBuilder) -> SdkResult<Response> {
+        let query = [("dataId", data_id.to_string()), ("group", group.to_string()), ("tenant", self.namespace.clone())];
+        let first = self.current.load(Ordering::Relaxed);
+        let mut tried = 0;
+        let resp = loop {
+            let index = (first + tried) % self.bases.len();
+            tried += 1;
+            match self.send_to(&self.bases[index], method.clone(), &query, &build).await {
+                Ok(resp) => {
+                    self.current.store(index, Ordering::Relaxed);
+                    break resp;
+                }
+                Err(e) if tried < self.bases.len() && is_unanswered(&e) => {
+                    debug!(base = %self.bases[index], error = %e, "Nacos server did not answer, trying the next one");
+                }
+                // Reported like a rejected gRPC request, so it is not retried and maps to an `AuthError`
+                Err(NacosError::AuthError(msg, _)) => return Err(SdkError::ErrResponse(None, 403, 403, Some(msg))),
+                Err(e) => return Err(SdkError::ErrResult(e.to_string())),
+            }
+        };
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(resp);
+        }
+        if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) {
+            // Nacos rejected the token, e.g. after a restart with a new secret, so the next request logs in again
+            self.token.lock().await.take();
+        }
+        let body = resp.text().await.ok();
+        let key = format!("dataId: {}, group: {}, tenant: {}", data_id, group, self.namespace);
+        Err(match status {
+            StatusCode::NOT_FOUND => SdkError::ConfigNotFound(key),
+            StatusCode::CONFLICT => SdkError::ConfigQueryConflict(key),
+            StatusCode::BAD_REQUEST => SdkError::InvalidParam(key, body.unwrap_or_default()),
+            status => SdkError::ErrResponse(None, i32::from(status.as_u16()), i32::from(status.as_u16()), body),
+        })
+    }
+
+    async fn send_to(
+        &self,
+        base: &str,
+        method: reqwest::Method,
+        query: &[(&str, String)],
+        build: &impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<Response, NacosError> {
+        let mut query = query.to_vec();
+        if let Some(token) = self.token(base).await? {
+            query.push(("accessToken", token));
+        }
+        build(self.client.request(method, format!("{}/v1/cs/configs", base)).query(&query))
+            .send()
+            .await
+            .map_err(|e| NacosError::NacosConnectionError(format!("Failed to call nacos: {}/v1/cs/configs: {}", base, e), Some(e.into())))
+    }
+
+    /// Nacos answers write requests with `true` or `false`
+    async fn accepted(resp: Response) -> SdkResult<bool> {
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| SdkError::ErrResult(format!("Failed to read nacos's response: {}", e)))?;
+        Ok(body.trim() == "true")
+    }
+
+    fn unsupported_listener() -> SdkError {
+        SdkError::InvalidParam("listener".to_string(), "Listening for changes is not supported over the HTTP protocol".to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl ConfigService for HttpConfigService {
+    async fn get_config(&self, data_id: String, group: String) -> SdkResult<ConfigResponse> {
+        let resp = self.send(reqwest::Method::GET, &data_id, &group, |request| request).await?;
+        let header = |name: &str| {
+            resp.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let md5 = header("Content-MD5");
+        let content_type = header("Config-Type");
+        let body = resp
+            .bytes()
+            .await
+            .map_err(|e| SdkError::ErrResult(format!("Failed to read nacos's response: {}", e)))?;
+        // Replacing invalid bytes changes the digest, so the bytes from nacos are checked instead
+        let raw_digest = match &self.hasher {
+            Some(hasher) if self.lossy_utf8 && crate::charset::is_utf8(self.charset.as_deref()) && std::str::from_utf8(&body).is_err() => {
+                Some(hasher.hex_digest(&body))
+            }
+            _ => None,
+        };
+        // Outside `with_raw_digest`, e.g. without a digest check, there is nobody to tell
+        let _ = RAW_DIGEST.try_with(|digest| digest.set(raw_digest));
+        // Not retried, decoding the same bytes again fails the same way
+        let content = crate::charset::decode(body.to_vec(), self.charset.as_deref(), self.lossy_utf8)
+            .map_err(|e| SdkError::InvalidParam(CHARSET_PARAM.to_string(), e.to_string()))?;
+        Ok(ConfigResponse::new(data_id, group, self.namespace.clone(), content, content_type, md5))
+    }
+
+    async fn publish_config(&self, data_id: String, group: String, content: String, content_type: Option<String>) -> SdkResult<bool> {
+        self.publish_config_param(data_id, group, content, content_type, None, HashMap::new()).await
+    }
+
+    async fn publish_config_cas(
+        &self,
+        data_id: String,
+        group: String,
+        content: String,
+        content_type: Option<String>,
+        cas_md5: String,
+    ) -> SdkResult<bool> {
+        self.publish_config_param(data_id, group, content, content_type, Some(cas_md5), HashMap::new()).await
+    }
+
+    async fn publish_config_beta(
+        &self,
+        data_id: String,
+        group: String,
+        content: String,
+        content_type: Option<String>,
+        beta_ips: String,
+    ) -> SdkResult<bool> {
+        let params = HashMap::from([("betaIps".to_string(), beta_ips)]);
+        self.publish_config_param(data_id, group, content, content_type, None, params).await
+    }
+
+    async fn publish_config_param(
+        &self,
+        data_id: String,
+        group: String,
+        content: String,
+        content_type: Option<String>,
+        cas_md5: Option<String>,
+        mut params: HashMap<String, String>,
+    ) -> SdkResult<bool> {
+        // The OpenAPI takes the beta IPs as a header, everything else as form fields
+        let beta_ips = params.remove("betaIps");
+        let mut form: Vec<(&str, String)> = vec![("content", content)];
+        form.extend(content_type.map(|content_type| ("type", content_type)));
+        form.extend(cas_md5.map(|cas_md5| ("casMd5", cas_md5)));
+        form.extend(self.app_name.clone().map(|app_name| ("appName", app_name)));
+        form.extend(params.iter().map(|(key, value)| (key.as_str(), value.clone())));
+        let resp = self
+            .send(reqwest::Method::POST, &data_id, &group, |request| match &beta_ips {
+                Some(beta_ips) => request.header("betaIps", beta_ips).form(&form),
+                None => request.form(&form),
+            })
+            .await?;
+        HttpConfigService::accepted(resp).await
+    }
+
+    async fn remove_config(&self, data_id: String, group: String) -> SdkResult<bool> {
+        let resp = self.send(reqwest::Method::DELETE, &data_id, &group, |request| request).await?;
+        HttpConfigService::accepted(resp).await
+    }
+
+    async fn add_listener(&self, _data_id: String, _group: String, _listener: Arc<dyn ConfigChangeListener>) -> SdkResult<()> {
+        Err(HttpConfigService::unsupported_listener())
+    }
+
+    async fn remove_listener(&self, _data_id: String, _group: String, _listener: Arc<dyn ConfigChangeListener>) -> SdkResult<()> {
+        Err(HttpConfigService::unsupported_listener())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_urls_cover_every_server_in_order() {
+        let cfg = NacosConfig { addr: "a, http://b:1/nacos".to_string(), ..Default::default() };
+        assert_eq!(base_urls(&cfg).unwrap(), ["http://a:8848/nacos", "http://b:1/nacos"]);
+        let cfg = NacosConfig { addr: "https://a,b".to_string(), ..Default::default() };
+        assert_eq!(base_urls(&cfg).unwrap(), ["https://a:8848/nacos", "https://b:8848/nacos"]);
+    }
+
+    #[test]
+    fn with_raw_digest_returns_the_digest_of_the_last_fetch() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let (_, digest) = runtime.block_on(with_raw_digest(async {
+            RAW_DIGEST.with(|digest| digest.set(Some("first".to_string())));
+            RAW_DIGEST.with(|digest| digest.set(None));
+        }));
+        assert_eq!(digest, None);
+        let (output, digest) = runtime.block_on(with_raw_digest(async {
+            RAW_DIGEST.with(|digest| digest.set(Some("raw".to_string())));
+            1
+        }));
+        assert_eq!((output, digest.as_deref()), (1, Some("raw")));
+    }
+
+    #[test]
+    fn only_transport_failures_move_on_to_the_next_server() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        // Nothing listens on port 1 of the loopback address
+        let refused = runtime.block_on(reqwest::Client::new().get("http://127.0.0.1:1/").send()).unwrap_err();
+        let e = NacosError::NacosConnectionError("Failed to call nacos".to_string(), Some(refused.into()));
+        assert!(is_unanswered(&e));
+        assert!(!is_unanswered(&NacosError::NacosConnectionError("Failed to call nacos".to_string(), None)));
+        assert!(!is_unanswered(&NacosError::AuthError("rejected".to_string(), None)));
+    }
+}
@@ -0,0 +1,98 @@
+//! OpenTelemetry spans for loading configs and decrypting passwords
+//!
+//! The spans are `tracing` spans using the field conventions of `tracing-opentelemetry`, so its
+//! layer exports them with `otel.kind`, the outcome as `otel.status_code` and errors as span
+//! events. Without the `otel` feature the functions only await the operation. Secret values are
+//! never recorded.
+
+use crate::NacosError;
+use std::future::Future;
+
+/// Run `from_nacos` in a `nacos.from_nacos` span, the config is recorded by [`record_config`]
+pub(crate) async fn observe_fetch<T>(op: impl Future<Output = Result<T, NacosError>>) -> Result<T, NacosError> {
+    #[cfg(feature = "otel")]
+    let op = imp::observe(
+        tracing::info_span!(
+            "nacos.from_nacos",
+            otel.kind = "client",
+            data_id = tracing::field::Empty,
+            group = tracing::field::Empty,
+            namespace = tracing::field::Empty,
+            duration_ms = tracing::field::Empty,
+            outcome = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+            otel.status_message = tracing::field::Empty,
+        ),
+        op,
+    );
+    op.await
+}
+
+/// Run a password decryption in a `nacos.decrypt_password` span, naming the backend only
+pub(crate) async fn observe_decrypt<T>(_password: &str, op: impl Future<Output = Result<T, NacosError>>) -> Result<T, NacosError> {
+    #[cfg(feature = "otel")]
+    let op = imp::observe(
+        tracing::info_span!(
+            "nacos.decrypt_password",
+            otel.kind = "internal",
+            backend = imp::backend(_password),
+            duration_ms = tracing::field::Empty,
+            outcome = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+            otel.status_message = tracing::field::Empty,
+        ),
+        op,
+    );
+    op.await
+}
+
+/// Record the config the current `nacos.from_nacos` span fetches, once it is known
+pub(crate) fn record_config(_data_id: &str, _group: &str, _namespace: &str) {
+    #[cfg(feature = "otel")]
+    {
+        let span = tracing::Span::current();
+        span.record("data_id", _data_id);
+        span.record("group", _group);
+        span.record("namespace", _namespace);
+    }
+}
+
+#[cfg(feature = "otel")]
+mod imp {
+    use crate::{
+        secret::{split_encrypted, Backend},
+        NacosError,
+    };
+    use std::{future::Future, time::Instant};
+    use tracing::{Instrument, Span};
+
+    pub(super) async fn observe<T>(span: Span, op: impl Future<Output = Result<T, NacosError>>) -> Result<T, NacosError> {
+        let started = Instant::now();
+        let result = op.instrument(span.clone()).await;
+        span.record("duration_ms", started.elapsed().as_millis() as u64);
+        match &result {
+            Ok(_) => {
+                span.record("outcome", "ok");
+                span.record("otel.status_code", "OK");
+            }
+            // The message is the error kind, the event carries the redacted error message
+            Err(e) => {
+                span.record("outcome", e.kind());
+                span.record("otel.status_code", "ERROR");
+                span.record("otel.status_message", e.kind());
+                span.in_scope(|| tracing::error!(exception.type = e.kind(), exception.message = %e, "exception"));
+            }
+        }
+        result
+    }
+
+    /// Name of the backend decrypting `password`, without any part of the password itself
+    pub(super) fn backend(password: &str) -> &'static str {
+        match split_encrypted(password) {
+            None => "plaintext",
+            Some((Some(Backend::Kms), _)) => "kms",
+            Some((Some(Backend::Vault), _)) => "vault",
+            Some((None, _)) => "enc",
+        }
+    }
+}
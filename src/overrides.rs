@@ -0,0 +1,184 @@
+//! Targeted overrides of single fields in a JSON config, e.g. `NACOS_OVERRIDE_database.port=5433`
+//!
+//! The same mechanism overlays `{PREFIX}_*` variables for `from_nacos_with_env_overlay`, with `__`
+//! separating nested keys, e.g. `APP_DATABASE__PORT=5433`.
+
+use crate::NacosError;
+use serde_json::{Map, Value};
+use std::{collections::HashMap, env};
+
+/// Prefix of the environment variables holding overrides, followed by the dotted path
+pub(crate) const ENV_PREFIX: &str = "NACOS_OVERRIDE_";
+
+/// Separator of nested keys in overlay variables
+const OVERLAY_SEPARATOR: &str = "__";
+
+/// Overrides from the environment keyed by dotted path, `prefix` is the `{prefix}_` of the variables
+pub(crate) fn from_env(prefix: &str) -> HashMap<String, String> {
+    // Variables that are not valid unicode cannot hold a path, skip them rather than panic
+    env::vars_os()
+        .filter_map(|(name, value)| {
+            let (name, value) = (name.into_string().ok()?, value.into_string().ok()?);
+            let path = name.strip_prefix(prefix)?.strip_prefix(ENV_PREFIX)?;
+            (!path.is_empty()).then(|| (path.to_string(), value))
+        })
+        .collect()
+}
+
+/// Overlay values from the `{prefix}_*` variables keyed by dotted path
+///
+/// Keys match the fields of `config` ignoring case and underscores, so `APP_MAX_CONNECTIONS` sets
+/// `maxConnections`. Keys without a matching field are added in lowercase.
+pub(crate) fn overlay_from_env(prefix: &str, config: &Value) -> HashMap<String, String> {
+    let prefix = format!("{}_", prefix);
+    env::vars_os()
+        .filter_map(|(name, value)| {
+            let (name, value) = (name.into_string().ok()?, value.into_string().ok()?);
+            let key = name.strip_prefix(&prefix)?;
+            let segments: Vec<_> = key.split(OVERLAY_SEPARATOR).collect();
+            (!segments.iter().any(|segment| segment.is_empty())).then(|| (resolve_path(config, &segments), value))
+        })
+        .collect()
+}
+
+/// The dotted path for the key `segments`, spelled like the existing fields of `config`
+fn resolve_path(config: &Value, segments: &[&str]) -> String {
+    let normalize = |name: &str| name.replace(['_', '-'], "").to_ascii_lowercase();
+    let mut current = Some(config);
+    let mut path = Vec::new();
+    for segment in segments {
+        let existing = current
+            .and_then(Value::as_object)
+            .and_then(|object| object.keys().find(|name| normalize(name) == normalize(segment)));
+        let name = existing.cloned().unwrap_or_else(|| segment.to_ascii_lowercase());
+        current = current.and_then(|value| value.get(&name));
+        path.push(name);
+    }
+    path.join(".")
+}
+
+/// Set the field at every path to its override, coerced to the type of the value it replaces
+pub(crate) fn apply(config: &mut Value, overrides: &HashMap<String, String>) -> Result<(), NacosError> {
+    // Shorter paths first, so `a.b` replaced by an object can still be refined by `a.b.c`
+    let mut paths: Vec<_> = overrides.iter().collect();
+    paths.sort_by_key(|(path, _)| (path.matches('.').count(), path.as_str()));
+    for (path, raw) in paths {
+        let field = field(config, path)?;
+        *field = coerce(field, raw).ok_or_else(|| {
+            let raw = if crate::redact::is_secret_path(path) { "***" } else { raw.as_str() };
+            NacosError::EnvVarError(format!("Override for {} does not match the type of the existing value: {}", path, raw), None)
+        })?;
+    }
+    Ok(())
+}
+
+/// The field at the dotted `path`, missing objects on the way are created
+fn field<'a>(config: &'a mut Value, path: &str) -> Result<&'a mut Value, NacosError> {
+    let mut current = config;
+    for key in path.split('.') {
+        if current.is_null() {
+            *current = Value::Object(Map::new());
+        }
+        let Value::Object(object) = current else {
+            return Err(NacosError::EnvVarError(format!("Override for {} goes through a value that is not an object", path), None));
+        };
+        current = object.entry(key).or_insert(Value::Null);
+    }
+    Ok(current)
+}
+
+/// Parse `raw` as the JSON type of `existing`, a new field takes any JSON value or else a string
+fn coerce(existing: &Value, raw: &str) -> Option<Value> {
+    match existing {
+        Value::String(_) => Some(Value::String(raw.to_string())),
+        Value::Null => Some(serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))),
+        existing => {
+            let value: Value = serde_json::from_str(raw).ok()?;
+            let same_type = match (existing, &value) {
+                (Value::Bool(_), Value::Bool(_)) | (Value::Array(_), Value::Array(_)) | (Value::Object(_), Value::Object(_)) => true,
+                // An integer field must stay an integer, a float field accepts any number
+                (Value::Number(existing), Value::Number(value)) => existing.is_f64() || !value.is_f64(),
+                _ => false,
+            };
+            same_type.then_some(value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn overrides(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(path, value)| (path.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn dotted_paths_replace_nested_fields_with_their_type() {
+        let mut config = json!({"database": {"host": "db", "port": 5432, "ssl": false, "ratio": 0.5}});
+        let changes = overrides(&[("database.port", "5433"), ("database.ssl", "true"), ("database.host", "10"), ("database.ratio", "1")]);
+        apply(&mut config, &changes).unwrap();
+        assert_eq!(config, json!({"database": {"host": "10", "port": 5433, "ssl": true, "ratio": 1}}));
+    }
+
+    #[test]
+    fn missing_fields_and_objects_are_created() {
+        let mut config = json!({});
+        apply(&mut config, &overrides(&[("a.b.c", "1"), ("a.name", "x"), ("list", "[1, 2]")])).unwrap();
+        assert_eq!(config, json!({"a": {"b": {"c": 1}, "name": "x"}, "list": [1, 2]}));
+    }
+
+    #[test]
+    fn shorter_paths_apply_first() {
+        let mut config = json!({"a": {}});
+        apply(&mut config, &overrides(&[("a.b.c", "2"), ("a.b", r#"{"d": 1}"#)])).unwrap();
+        assert_eq!(config, json!({"a": {"b": {"c": 2, "d": 1}}}));
+    }
+
+    #[test]
+    fn type_mismatches_are_errors() {
+        for (path, raw) in [("port", "not-a-number"), ("port", "1.5"), ("ssl", "yes"), ("hosts", "{}")] {
+            let mut config = json!({"port": 5432, "ssl": false, "hosts": []});
+            let err = apply(&mut config, &overrides(&[(path, raw)])).unwrap_err();
+            assert!(matches!(err, NacosError::EnvVarError(..)), "{}={}: {:?}", path, raw, err);
+        }
+    }
+
+    #[test]
+    fn mismatch_errors_redact_secret_values() {
+        let mut config = json!({"database": {"password": 1}});
+        let err = apply(&mut config, &overrides(&[("database.password", "hunter2")])).unwrap_err();
+        assert!(!err.to_string().contains("hunter2"), "{}", err);
+        assert!(err.to_string().contains("***"), "{}", err);
+    }
+
+    #[test]
+    fn array_paths_are_not_indexed() {
+        // Only objects are traversed, `servers.0` does not address the first element
+        let mut config = json!({"servers": [{"port": 1}]});
+        let err = apply(&mut config, &overrides(&[("servers.0.port", "2")])).unwrap_err();
+        assert!(err.to_string().contains("not an object"), "{}", err);
+        assert_eq!(config, json!({"servers": [{"port": 1}]}));
+    }
+
+    #[test]
+    fn overlay_keys_follow_the_existing_spelling() {
+        let config = json!({"maxConnections": 1, "database": {"read_timeout": 2}});
+        assert_eq!(resolve_path(&config, &["MAX_CONNECTIONS"]), "maxConnections");
+        assert_eq!(resolve_path(&config, &["DATABASE", "READ_TIMEOUT"]), "database.read_timeout");
+        assert_eq!(resolve_path(&config, &["NEW", "FIELD"]), "new.field");
+    }
+
+    #[test]
+    fn variables_are_read_by_prefix() {
+        env::set_var("SOUTHEAST_OVERRIDES_TEST_NACOS_OVERRIDE_database.port", "5433");
+        env::set_var("SOUTHEAST_OVERRIDES_TEST_NACOS_OVERRIDE_", "ignored");
+        assert_eq!(from_env("SOUTHEAST_OVERRIDES_TEST_"), overrides(&[("database.port", "5433")]));
+
+        env::set_var("SOUTHEAST_OVERLAY_TEST_DATABASE__PORT", "5434");
+        env::set_var("SOUTHEAST_OVERLAY_TEST_BROKEN____KEY", "ignored");
+        let config = json!({"database": {"port": 1}});
+        assert_eq!(overlay_from_env("SOUTHEAST_OVERLAY_TEST", &config), overrides(&[("database.port", "5434")]));
+    }
+}
@@ -0,0 +1,292 @@
+//! Minimal parser for Java-style `.properties` content
+//!
+//! Keys containing dots are mapped to nested tables, so `database.port=5432` deserializes
+//! into a `database` struct with a `port` field. Duplicate keys take the last value.
+
+use serde::de::{
+    self,
+    value::{Error, MapDeserializer, SeqDeserializer},
+    DeserializeOwned, Deserializer, IntoDeserializer, Visitor,
+};
+use std::collections::BTreeMap;
+
+/// Deserialize `.properties` content into `T`
+pub(crate) fn from_str<T: DeserializeOwned>(content: &str) -> Result<T, Error> {
+    T::deserialize(Value::Table(parse(content)))
+}
+
+/// Parse the content into a tree of tables keyed by the dot separated key segments
+fn parse(content: &str) -> BTreeMap<String, Value> {
+    let mut table = BTreeMap::new();
+    for line in logical_lines(content) {
+        let (key, value) = split_entry(&line);
+        insert(&mut table, &key, value);
+    }
+    table
+}
+
+/// Join `\` continued lines and drop blank lines and comments
+fn logical_lines(content: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut pending: Option<String> = None;
+    for line in content.lines() {
+        // Leading whitespace is never significant, including on continuation lines
+        let line = line.trim_start();
+        let mut text = match pending.take() {
+            Some(text) => text,
+            None if line.is_empty() || line.starts_with('#') || line.starts_with('!') => continue,
+            None => String::new(),
+        };
+        if ends_with_continuation(line) {
+            text.push_str(&line[..line.len() - 1]);
+            pending = Some(text);
+        } else {
+            text.push_str(line);
+            lines.push(text);
+        }
+    }
+    lines.extend(pending);
+    lines
+}
+
+/// A line is continued when it ends with an odd number of backslashes
+fn ends_with_continuation(line: &str) -> bool {
+    line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+/// Split a logical line on the first unescaped `=` or `:`, a key without one has an empty value
+fn split_entry(line: &str) -> (String, String) {
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '=' | ':' => {
+                return (unescape(line[..index].trim()), unescape(line[index + 1..].trim()));
+            }
+            _ => {}
+        }
+    }
+    (unescape(line.trim()), String::new())
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+fn insert(table: &mut BTreeMap<String, Value>, key: &str, value: String) {
+    let Some((head, rest)) = key.split_once('.') else {
+        table.insert(key.to_string(), Value::Leaf(value));
+        return;
+    };
+    let entry = table.entry(head.to_string()).or_insert_with(|| Value::Table(BTreeMap::new()));
+    // A later dotted key replaces an earlier plain value with the same prefix
+    if let Value::Leaf(_) = entry {
+        *entry = Value::Table(BTreeMap::new());
+    }
+    if let Value::Table(child) = entry {
+        insert(child, rest, value);
+    }
+}
+
+/// A property value, parsed on demand into the type requested by `T`
+enum Value {
+    Leaf(String),
+    Table(BTreeMap<String, Value>),
+}
+
+impl Value {
+    fn into_leaf(self) -> Result<String, Error> {
+        match self {
+            Value::Leaf(value) => Ok(value),
+            Value::Table(_) => Err(de::Error::custom("expected a value, found nested keys")),
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                let value = self.into_leaf()?;
+                let parsed = value
+                    .parse()
+                    .map_err(|_| de::Error::custom(format!("invalid value: {}", value)))?;
+                visitor.$visit(parsed)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Leaf(value) => visitor.visit_string(value),
+            Value::Table(table) => MapDeserializer::new(table.into_iter()).deserialize_any(visitor),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // Comma separated lists, e.g. `hosts=a,b,c`
+        let value = self.into_leaf()?;
+        let items = value.split(',').map(|item| Value::Leaf(item.trim().to_string()));
+        SeqDeserializer::new(items).deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.into_leaf()?.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Database {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        name: String,
+        debug: bool,
+        database: Database,
+        hosts: Vec<String>,
+    }
+
+    fn strings(content: &str) -> HashMap<String, String> {
+        from_str(content).unwrap()
+    }
+
+    #[test]
+    fn dotted_keys_are_nested_and_typed() {
+        let content = "name=app\ndebug=true\ndatabase.host=db\ndatabase.port=5432\nhosts=a, b,c\n";
+        let config: Config = from_str(content).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                name: "app".to_string(),
+                debug: true,
+                database: Database { host: "db".to_string(), port: 5432 },
+                hosts: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn comments_blank_lines_and_separators() {
+        let values = strings("# comment\n! also a comment\n\n  a = 1\nb: 2\nc\n");
+        assert_eq!(values["a"], "1");
+        assert_eq!(values["b"], "2");
+        assert_eq!(values["c"], "");
+        assert_eq!(values.len(), 3);
+    }
+
+    #[test]
+    fn escapes_are_unescaped_in_keys_and_values() {
+        let values = strings("url\\:port=http\\://host\\:8848\nmulti=line1\\nline2\\tend\nback=c\\\\dir\n");
+        assert_eq!(values["url:port"], "http://host:8848");
+        assert_eq!(values["multi"], "line1\nline2\tend");
+        assert_eq!(values["back"], "c\\dir");
+    }
+
+    #[test]
+    fn continuation_lines_are_joined_without_leading_whitespace() {
+        let values = strings("list=a,\\\n    b,\\\n    c\nafter=1\n");
+        assert_eq!(values["list"], "a,b,c");
+        assert_eq!(values["after"], "1");
+    }
+
+    #[test]
+    fn an_even_number_of_backslashes_does_not_continue() {
+        let values = strings("path=c:\\\\\nnext=1\n");
+        assert_eq!(values["path"], "c:\\");
+        assert_eq!(values["next"], "1");
+    }
+
+    #[test]
+    fn quotes_are_part_of_the_value() {
+        let values = strings("greeting=\"hello world\"\nsingle='x'\n");
+        assert_eq!(values["greeting"], "\"hello world\"");
+        assert_eq!(values["single"], "'x'");
+    }
+
+    #[test]
+    fn later_keys_win() {
+        let values = strings("a=1\na=2\n");
+        assert_eq!(values["a"], "2");
+        let nested: HashMap<String, HashMap<String, String>> = from_str("a=plain\na.b=nested\n").unwrap();
+        assert_eq!(nested["a"]["b"], "nested");
+    }
+
+    #[test]
+    fn invalid_numbers_are_errors() {
+        let err = from_str::<Database>("host=db\nport=not-a-port\n").unwrap_err();
+        assert!(err.to_string().contains("not-a-port"), "{}", err);
+    }
+}
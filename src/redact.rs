@@ -0,0 +1,232 @@
+//! Masking of secrets in config content that ends up in error messages
+//!
+//! Values of keys with a `password`, `token`, `secret` or `key` segment, e.g. `db_password` or
+//! `apiKey` but not `monkey`, and encrypted `ENC(...)` values are replaced by `***`. Set
+//! `NACOS_REDACT_ERRORS=false` to see them while debugging.
+
+use std::{env, ops::Range};
+
+/// Segments of key names whose values are masked, compared case-insensitively
+const SECRET_KEYS: [&str; 5] = ["password", "token", "secret", "key", "apikey"];
+
+/// Whether redaction is on, it is unless `NACOS_REDACT_ERRORS` is `false`
+fn enabled() -> bool {
+    !matches!(env::var("NACOS_REDACT_ERRORS").map(|value| value.to_ascii_lowercase()).as_deref(), Ok("false" | "0" | "no"))
+}
+
+/// Whether values of the key `name` are masked, e.g. `db_password` or `apiKey`
+///
+/// The name is split into segments at `_`, `-`, `.` and lower-to-upper case changes, one of
+/// which must be a secret key or its plural.
+pub(crate) fn is_secret_key(name: &str) -> bool {
+    segments(name).iter().any(|segment| {
+        // Plurals count too, e.g. `tokens` or `api_keys`
+        let segment = segment.strip_suffix(['s', 'S']).filter(|singular| !singular.is_empty()).unwrap_or(segment);
+        SECRET_KEYS.iter().any(|key| segment.eq_ignore_ascii_case(key))
+    })
+}
+
+/// The segments of a key name, `apiKey` and `api_key` both give `api` and `key`
+fn segments(name: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<char> = None;
+    for (index, c) in name.char_indices() {
+        if matches!(c, '_' | '-' | '.') {
+            segments.push(&name[start..index]);
+            start = index + 1;
+        } else if c.is_uppercase() && previous.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit()) {
+            segments.push(&name[start..index]);
+            start = index;
+        }
+        previous = Some(c);
+    }
+    segments.push(&name[start..]);
+    segments
+}
+
+/// Whether any segment of the dotted path, e.g. `database.password`, is a secret key
+pub(crate) fn is_secret_path(path: &str) -> bool {
+    path.split(['.', '[', ']']).any(is_secret_key)
+}
+
+/// The byte range `window` of `text` with secret values masked, unchanged when redaction is off
+///
+/// The secrets are found in the whole `text`, so a value whose key lies outside the window is
+/// still masked.
+pub(crate) fn redact(text: &str, window: Range<usize>) -> String {
+    if !enabled() {
+        return text[window].to_string();
+    }
+    let mut out = String::with_capacity(window.len());
+    let mut at = window.start;
+    for secret in secret_ranges(text) {
+        // A value ending where the window starts is not in it, an empty one is masked where it is
+        if secret.end < at || (secret.end == at && !secret.is_empty()) || secret.start >= window.end {
+            continue;
+        }
+        out.push_str(&text[at..secret.start.max(at)]);
+        out.push_str("***");
+        at = secret.end.min(window.end);
+    }
+    out.push_str(&text[at..window.end]);
+    out
+}
+
+/// Byte ranges of the secret values in `text`, sorted and not overlapping
+fn secret_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = secret_values(text);
+    ranges.extend(encrypted_values(text));
+    ranges.sort_by_key(|range| (range.start, range.end));
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end && !range.is_empty() => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Ranges of the values after every `key: value`, `"key": "value"` or `key=value` with a secret key
+///
+/// Quotes are not part of the range, so they survive the masking.
+fn secret_values(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    while let Some(key_end) = next_secret_key_end(&text[offset..]) {
+        let after_key = &text[offset + key_end..];
+        let separator = after_key.trim_start_matches(['"', '\'']).trim_start();
+        let Some(value) = separator.strip_prefix([':', '=']) else {
+            offset += key_end;
+            continue;
+        };
+        let value = value.trim_start();
+        let value_start = text.len() - value.len();
+        // Nested objects and lists are not a single secret value, their keys are checked on their own
+        if !value.is_empty() && !value.starts_with(['{', '[']) {
+            let len = value_len(value);
+            ranges.push(match value.chars().next() {
+                Some(quote @ ('"' | '\'')) if len > 1 && value[..len].ends_with(quote) => value_start + 1..value_start + len - 1,
+                Some('"' | '\'') => value_start + 1..value_start + len,
+                _ => value_start..value_start + len,
+            });
+            offset = value_start + len;
+        } else {
+            offset = value_start;
+        }
+    }
+    ranges
+}
+
+/// End of the first key name in `text` that has a secret key segment
+fn next_secret_key_end(text: &str) -> Option<usize> {
+    let is_key_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+    let mut start = 0;
+    while start < text.len() {
+        let word_start = start + text[start..].find(is_key_char)?;
+        let word_len = text[word_start..].find(|c: char| !is_key_char(c)).unwrap_or(text.len() - word_start);
+        let word_end = word_start + word_len;
+        if is_secret_key(&text[word_start..word_end]) {
+            return Some(word_end);
+        }
+        start = word_end;
+    }
+    None
+}
+
+/// Length of the value at the start of `value`, a quoted string or up to the next delimiter
+fn value_len(value: &str) -> usize {
+    match value.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let mut escaped = false;
+            for (index, c) in value.char_indices().skip(1) {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    c if c == quote => return index + 1,
+                    _ => {}
+                }
+            }
+            value.len()
+        }
+        _ => value.find([',', '}', ']', '\n', '\r', ' ', '\t']).unwrap_or(value.len()),
+    }
+}
+
+/// Ranges of the ciphertext of every `ENC(...)`, `KMS(...)` and `VAULT(...)` value
+fn encrypted_values(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    for value in crate::secret::find_encrypted(text) {
+        let prefix_len = value.find('(').unwrap_or(0) + 1;
+        for (start, _) in text.match_indices(value) {
+            ranges.push(start + prefix_len..start + value.len() - 1);
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redact_all(text: &str) -> String {
+        redact(text, 0..text.len())
+    }
+
+    #[test]
+    fn secret_keys_match_whole_segments() {
+        for name in ["password", "db_password", "tokens", "api_keys", "apiKey", "api_key", "API-KEY", "APIKey", "secretKey", "access.token", "key"] {
+            assert!(is_secret_key(name), "{}", name);
+        }
+        for name in ["monkey", "keyboard_layout", "tokenizer", "hotkeys", "keyss", "name"] {
+            assert!(!is_secret_key(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn secret_paths_match_any_segment() {
+        assert!(is_secret_path("database.password"));
+        assert!(is_secret_path("credentials[0].apiKey"));
+        assert!(is_secret_path("tokens[0]"));
+        assert!(!is_secret_path("database.port"));
+        assert!(!is_secret_path("servers[1].monkey"));
+    }
+
+    #[test]
+    fn json_yaml_and_properties_values_are_masked() {
+        assert_eq!(redact_all(r#"{"password": "hunter2", "port": 5432}"#), r#"{"password": "***", "port": 5432}"#);
+        assert_eq!(redact_all("token: abc123\nname: app"), "token: ***\nname: app");
+        assert_eq!(redact_all("db.password=hunter2\ndb.host=db"), "db.password=***\ndb.host=db");
+        assert_eq!(redact_all(r#"{"secret": "a \"quoted\" value", "n": 1}"#), r#"{"secret": "***", "n": 1}"#);
+        assert_eq!(redact_all(r#"{"password": ""}"#), r#"{"password": "***"}"#);
+    }
+
+    #[test]
+    fn fields_that_only_contain_a_secret_word_are_kept() {
+        let text = r#"{"monkey": "banana", "keyboard_layout": "us"}"#;
+        assert_eq!(redact_all(text), text);
+    }
+
+    #[test]
+    fn nested_values_are_checked_by_their_own_keys() {
+        let text = r#"{"secret": {"name": "app", "password": "hunter2"}}"#;
+        assert_eq!(redact_all(text), r#"{"secret": {"name": "app", "password": "***"}}"#);
+    }
+
+    #[test]
+    fn encrypted_values_keep_their_prefix() {
+        assert_eq!(redact_all(r#"{"dsn": "ENC(abc==)", "b": "VAULT(vault:v1:x)"}"#), r#"{"dsn": "ENC(***)", "b": "VAULT(***)"}"#);
+    }
+
+    #[test]
+    fn a_window_masks_values_whose_key_lies_outside() {
+        let text = r#"{"password": "hunter2", "port": 5432}"#;
+        let start = text.find("ter2").unwrap();
+        assert_eq!(redact(text, start..text.len()), r#"***", "port": 5432}"#);
+        let end = text.find("hun").unwrap() + 2;
+        assert_eq!(redact(text, 0..end), r#"{"password": "***"#);
+        let port = text.find("\"port").unwrap();
+        assert_eq!(redact(text, port..text.len()), r#""port": 5432}"#);
+    }
+}
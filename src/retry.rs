@@ -0,0 +1,85 @@
+//! Retry with exponential backoff for transient Nacos failures
+
+use nacos_sdk::api::error::Error as SdkError;
+use rand::Rng;
+use serde::Serialize;
+use std::{future::Future, time::Duration};
+
+/// How often and how long to wait when fetching a config from Nacos fails
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RetryPolicy {
+    /// Total number of attempts including the first one, `1` disables retries
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled on every further retry
+    pub base_delay: Duration,
+    /// Upper bound for the delay between two attempts
+    pub max_delay: Duration,
+    /// Randomize each delay between half and the full value
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that only makes a single attempt
+    pub fn no_retry() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Delay before the given retry, starting at `1` for the first retry
+    fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if self.jitter {
+            delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+        } else {
+            delay
+        }
+    }
+
+    /// Run `op` until it succeeds, fails with a non transient error or runs out of attempts
+    pub(crate) async fn run<T, F, Fut>(&self, mut op: F) -> Result<T, SdkError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, SdkError>>,
+    {
+        let mut retry = 0;
+        loop {
+            match op().await {
+                Err(e) if retry + 1 < self.max_attempts && is_transient(&e) => {
+                    retry += 1;
+                    tokio::time::sleep(self.delay(retry)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Errors that will not go away by retrying, everything else may be a network hiccup
+pub(crate) fn is_transient(e: &SdkError) -> bool {
+    !matches!(
+        e,
+        SdkError::ConfigNotFound(_)
+            | SdkError::InvalidParam(..)
+            | SdkError::Serialization(_)
+            | SdkError::WrongServerAddress(_)
+    ) && !is_auth_failure(e)
+}
+
+/// Nacos rejected the credentials, it answers with 403 (or 401) when the login failed
+pub(crate) fn is_auth_failure(e: &SdkError) -> bool {
+    matches!(e, SdkError::ErrResponse(_, result_code, error_code, _) if [result_code, error_code].iter().any(|code| matches!(code, 401 | 403)))
+}
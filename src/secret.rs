@@ -0,0 +1,246 @@
+//! Pluggable backends for decrypting `KMS(...)`, `VAULT(...)` and `ENC(...)` passwords
+
+use crate::{NacosConfig, NacosError};
+use std::{env, fmt};
+
+/// A secret backend that turns the ciphertext inside `ENC(...)` and friends into the plaintext password
+#[async_trait::async_trait]
+pub trait SecretDecryptor: Send + Sync {
+    /// Decrypt `ciphertext`, the content of an encrypted value without the prefix and parentheses
+    async fn decrypt(&self, ciphertext: &str) -> Result<String, NacosError>;
+
+    /// Decrypt `ciphertext` and report how, see [`DecryptedSecret`]
+    ///
+    /// Backends that are not AWS KMS can rely on the default, which calls `decrypt`.
+    async fn decrypt_secret(&self, ciphertext: &str) -> Result<DecryptedSecret, NacosError> {
+        Ok(DecryptedSecret {
+            value: self.decrypt(ciphertext).await?,
+            via_kms: false,
+            key_id: None,
+            cached: false,
+        })
+    }
+}
+
+/// A decrypted password and how it was decrypted, for audit logs
+///
+/// `Debug` hides the plaintext, so the whole value can be logged.
+#[derive(Clone, PartialEq)]
+pub struct DecryptedSecret {
+    /// The plaintext, the password itself when it was not encrypted
+    pub value: String,
+    /// Whether AWS KMS decrypted the value
+    pub via_kms: bool,
+    /// ARN of the KMS key that decrypted the value
+    pub key_id: Option<String>,
+    /// Whether the value came from the cache, so no decryption happened for this call
+    pub cached: bool,
+}
+
+impl DecryptedSecret {
+    /// A password that was not encrypted
+    pub(crate) fn plaintext(value: &str) -> Self {
+        DecryptedSecret {
+            value: value.to_string(),
+            via_kms: false,
+            key_id: None,
+            cached: false,
+        }
+    }
+}
+
+impl fmt::Debug for DecryptedSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecryptedSecret")
+            .field("value", &"***")
+            .field("via_kms", &self.via_kms)
+            .field("key_id", &self.key_id)
+            .field("cached", &self.cached)
+            .finish()
+    }
+}
+
+/// Secret backend named by the prefix of an encrypted password
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Backend {
+    Kms,
+    Vault,
+}
+
+/// Split `KMS(...)`, `VAULT(...)` or `ENC(...)` into its backend and ciphertext
+///
+/// `ENC(...)` does not name a backend, `None` is returned for plaintext passwords.
+pub(crate) fn split_encrypted(password: &str) -> Option<(Option<Backend>, &str)> {
+    let (backend, rest) = if let Some(rest) = password.strip_prefix("KMS(") {
+        (Some(Backend::Kms), rest)
+    } else if let Some(rest) = password.strip_prefix("VAULT(") {
+        (Some(Backend::Vault), rest)
+    } else {
+        (None, password.strip_prefix("ENC(")?)
+    };
+    Some((backend, rest.strip_suffix(')')?))
+}
+
+/// Every distinct `KMS(...)`, `VAULT(...)` or `ENC(...)` value embedded in `content`
+///
+/// A prefix only counts at the start of a word, so `XENC(` is not mistaken for a value.
+pub(crate) fn find_encrypted(content: &str) -> Vec<&str> {
+    let mut found = Vec::new();
+    for prefix in ["KMS(", "VAULT(", "ENC("] {
+        for (start, _) in content.match_indices(prefix) {
+            let at_word_start = !content[..start].ends_with(|c: char| c.is_alphanumeric() || c == '_');
+            let Some(len) = content[start + prefix.len()..].find(')') else {
+                continue;
+            };
+            let value = &content[start..start + prefix.len() + len + 1];
+            if at_word_start && len > 0 && !found.contains(&value) {
+                found.push(value);
+            }
+        }
+    }
+    found
+}
+
+/// `content` with every encrypted value replaced by its plaintext, escaped as a JSON string in `json` content
+pub(crate) fn replace_encrypted(content: &str, values: &[&str], plaintexts: Vec<String>, json: bool) -> Result<String, NacosError> {
+    let mut content = content.to_string();
+    for (value, plaintext) in values.iter().zip(plaintexts) {
+        let plaintext = if json {
+            // Quoted JSON string without the quotes
+            let quoted = serde_json::to_string(&plaintext)?;
+            quoted[1..quoted.len() - 1].to_string()
+        } else {
+            plaintext
+        };
+        content = content.replace(value, &plaintext);
+    }
+    Ok(content)
+}
+
+/// Pick the backend for `ciphertext`
+///
+/// An explicit `KMS(...)` or `VAULT(...)` prefix wins. For `ENC(...)`, `SECRET_BACKEND`
+/// (`kms` or `vault`) takes precedence, otherwise Vault transit ciphertext (`vault:v1:...`)
+/// goes to Vault and everything else to AWS KMS.
+pub(crate) fn resolve_backend(backend: Option<Backend>, ciphertext: &str) -> Result<Backend, NacosError> {
+    match backend {
+        Some(backend) => Ok(backend),
+        None => default_backend(ciphertext),
+    }
+}
+
+/// The decryptor of `backend` with the settings of `cfg`
+pub(crate) fn decryptor_for(backend: Backend, cfg: &NacosConfig) -> Result<Box<dyn SecretDecryptor>, NacosError> {
+    match backend {
+        Backend::Kms => kms_decryptor(cfg),
+        Backend::Vault => vault_decryptor(),
+    }
+}
+
+/// Key of the decrypted `password` in the cache, covering the backend and its settings
+///
+/// The same ciphertext decrypted under another region, endpoint, encryption context or Vault
+/// server is a separate entry, so one setting never serves the plaintext of another. `None`
+/// for a prebuilt KMS client, whose settings cannot be told apart.
+pub(crate) fn cache_key(backend: Backend, password: &str, cfg: &NacosConfig) -> Option<String> {
+    let identity = match backend {
+        #[cfg(feature = "kms")]
+        Backend::Kms if cfg.kms_client.is_some() => return None,
+        Backend::Kms => {
+            let mut context: Vec<_> = cfg.kms_encryption_context.iter().collect();
+            context.sort();
+            format!("kms\0{:?}\0{:?}\0{:?}", cfg.kms_region, cfg.kms_endpoint, context)
+        }
+        Backend::Vault => {
+            let var = |name: &str| env::var(name).unwrap_or_default();
+            format!("vault\0{}\0{}\0{}", var("VAULT_ADDR"), var("VAULT_TRANSIT_MOUNT"), var("VAULT_TRANSIT_KEY"))
+        }
+    };
+    Some(format!("{}\0{}", identity, password))
+}
+
+fn default_backend(ciphertext: &str) -> Result<Backend, NacosError> {
+    match env::var("SECRET_BACKEND") {
+        Ok(backend) if !backend.trim().is_empty() => match backend.trim().to_ascii_lowercase().as_str() {
+            "kms" => Ok(Backend::Kms),
+            "vault" => Ok(Backend::Vault),
+            other => Err(NacosError::EnvVarError(format!("Unsupported SECRET_BACKEND, expected kms or vault: {}", other), None)),
+        },
+        _ if ciphertext.starts_with("vault:") => Ok(Backend::Vault),
+        _ => Ok(Backend::Kms),
+    }
+}
+
+#[cfg(feature = "kms")]
+fn kms_decryptor(cfg: &NacosConfig) -> Result<Box<dyn SecretDecryptor>, NacosError> {
+    Ok(Box::new(crate::KmsDecryptor::from_config(cfg)))
+}
+
+#[cfg(not(feature = "kms"))]
+fn kms_decryptor(_cfg: &NacosConfig) -> Result<Box<dyn SecretDecryptor>, NacosError> {
+    Err(NacosError::KmsError("Decrypting KMS(...) and ENC(...) passwords requires the `kms` feature".to_string(), None))
+}
+
+#[cfg(feature = "vault")]
+fn vault_decryptor() -> Result<Box<dyn SecretDecryptor>, NacosError> {
+    Ok(Box::new(crate::VaultDecryptor::from_env()?))
+}
+
+#[cfg(not(feature = "vault"))]
+fn vault_decryptor() -> Result<Box<dyn SecretDecryptor>, NacosError> {
+    Err(NacosError::VaultError("Decrypting VAULT(...) passwords requires the `vault` feature".to_string(), None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_encrypted_names_the_backend() {
+        assert_eq!(split_encrypted("KMS(abc)"), Some((Some(Backend::Kms), "abc")));
+        assert_eq!(split_encrypted("VAULT(vault:v1:x)"), Some((Some(Backend::Vault), "vault:v1:x")));
+        assert_eq!(split_encrypted("ENC(abc)"), Some((None, "abc")));
+        assert_eq!(split_encrypted("plain"), None);
+        assert_eq!(split_encrypted("ENC(unterminated"), None);
+    }
+
+    #[test]
+    fn find_encrypted_lists_each_value_once() {
+        let content = r#"{"a": "ENC(x1)", "b": "KMS(x2)", "c": "VAULT(vault:v1:x3)", "d": "ENC(x1)"}"#;
+        assert_eq!(find_encrypted(content), vec!["KMS(x2)", "VAULT(vault:v1:x3)", "ENC(x1)"]);
+    }
+
+    #[test]
+    fn find_encrypted_only_matches_at_word_starts() {
+        assert!(find_encrypted("XENC(a) my_ENC(b) ENC() ENC(open").is_empty());
+        assert_eq!(find_encrypted("url=jdbc:ENC(a)"), vec!["ENC(a)"]);
+    }
+
+    #[test]
+    fn replacement_escapes_plaintext_in_json() {
+        let content = r#"{"a": "ENC(x1)", "b": "ENC(x2)", "c": "ENC(x1)"}"#;
+        let values = find_encrypted(content);
+        let replaced = replace_encrypted(content, &values, vec!["say \"hi\"".to_string(), "back\\slash".to_string()], true).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&replaced).unwrap();
+        assert_eq!(value["a"], "say \"hi\"");
+        assert_eq!(value["b"], "back\\slash");
+        assert_eq!(value["c"], "say \"hi\"");
+    }
+
+    #[test]
+    fn replacement_keeps_plaintext_as_is_elsewhere() {
+        let replaced = replace_encrypted("password=ENC(x1)", &["ENC(x1)"], vec!["a\"b".to_string()], false).unwrap();
+        assert_eq!(replaced, "password=a\"b");
+    }
+
+    #[test]
+    fn cache_keys_differ_per_backend_settings() {
+        let cfg = NacosConfig::default();
+        let other_region = NacosConfig { kms_region: Some("eu-west-1".to_string()), ..NacosConfig::default() };
+        let key = cache_key(Backend::Kms, "ENC(x)", &cfg);
+        assert_eq!(key, cache_key(Backend::Kms, "ENC(x)", &cfg.clone()));
+        assert_ne!(key, cache_key(Backend::Kms, "ENC(x)", &other_region));
+        assert_ne!(key, cache_key(Backend::Vault, "ENC(x)", &cfg));
+        assert_ne!(key, cache_key(Backend::Kms, "ENC(y)", &cfg));
+    }
+}
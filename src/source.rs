@@ -0,0 +1,122 @@
+//! Where configs come from, so code can be generic over Nacos and an in-memory fake
+
+use crate::{non_empty_content, NacosClient, NacosError};
+use std::collections::HashMap;
+
+/// A source of config content, parsed by [`crate::from_source`]
+#[async_trait::async_trait]
+pub trait ConfigSource: Send + Sync {
+    /// The content of the config, a `ConfigNotFound` or `EmptyConfig` when there is none
+    async fn fetch(&self, data_id: &str, group: &str) -> Result<String, NacosError>;
+}
+
+#[async_trait::async_trait]
+impl ConfigSource for NacosClient {
+    /// The content verified and decoded as [`NacosClient::get`] reads it
+    async fn fetch(&self, data_id: &str, group: &str) -> Result<String, NacosError> {
+        let resp = self.get_response(data_id, group).await?;
+        non_empty_content(&resp).map(str::to_string)
+    }
+}
+
+/// An in-memory [`ConfigSource`] for tests
+#[derive(Debug, Clone, Default)]
+pub struct StaticConfigSource {
+    configs: HashMap<(String, String), String>,
+}
+
+impl StaticConfigSource {
+    pub fn new() -> Self {
+        StaticConfigSource::default()
+    }
+
+    /// Add the content of a config, replacing any previous content
+    pub fn with(mut self, data_id: &str, group: &str, content: &str) -> Self {
+        self.insert(data_id, group, content);
+        self
+    }
+
+    /// Set the content of a config, replacing any previous content
+    pub fn insert(&mut self, data_id: &str, group: &str, content: &str) {
+        self.configs.insert((data_id.to_string(), group.to_string()), content.to_string());
+    }
+
+    /// Remove a config, later fetches fail with `ConfigNotFound`
+    pub fn remove(&mut self, data_id: &str, group: &str) {
+        self.configs.remove(&(data_id.to_string(), group.to_string()));
+    }
+}
+
+#[async_trait::async_trait]
+impl ConfigSource for StaticConfigSource {
+    async fn fetch(&self, data_id: &str, group: &str) -> Result<String, NacosError> {
+        let content = self.configs.get(&(data_id.to_string(), group.to_string())).ok_or_else(|| NacosError::ConfigNotFound {
+            data_id: data_id.to_string(),
+            group: group.to_string(),
+        })?;
+        if content.trim().is_empty() {
+            return Err(NacosError::EmptyConfig {
+                data_id: data_id.to_string(),
+                group: group.to_string(),
+            });
+        }
+        Ok(content.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::future::Future;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        port: u16,
+    }
+
+    #[test]
+    fn static_source_serves_configs_by_data_id_and_group() {
+        let source = StaticConfigSource::new().with("app", "G", r#"{"port": 1}"#).with("app", "OTHER", r#"{"port": 2}"#);
+        assert_eq!(block_on(source.fetch("app", "G")).unwrap(), r#"{"port": 1}"#);
+        let config: Config = block_on(crate::from_source(&source, "app", "OTHER")).unwrap();
+        assert_eq!(config, Config { port: 2 });
+    }
+
+    #[test]
+    fn inserts_replace_and_removes_forget() {
+        let mut source = StaticConfigSource::new().with("app", "G", r#"{"port": 1}"#);
+        source.insert("app", "G", r#"{"port": 3}"#);
+        let config: Config = block_on(crate::from_source(&source, "app", "G")).unwrap();
+        assert_eq!(config.port, 3);
+        source.remove("app", "G");
+        assert!(block_on(source.fetch("app", "G")).unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn missing_and_blank_configs_are_errors() {
+        let source = StaticConfigSource::new().with("blank", "G", " \n");
+        let err = block_on(source.fetch("missing", "G")).unwrap_err();
+        assert!(matches!(err, NacosError::ConfigNotFound { ref data_id, ref group } if data_id == "missing" && group == "G"), "{:?}", err);
+        let err = block_on(source.fetch("blank", "G")).unwrap_err();
+        assert!(matches!(err, NacosError::EmptyConfig { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn from_source_reports_parse_errors() {
+        let source = StaticConfigSource::new().with("app", "G", r#"{"port": "high"}"#);
+        let err = block_on(crate::from_source::<Config, _>(&source, "app", "G")).unwrap_err();
+        assert!(matches!(err, NacosError::ConfigParseError(..)), "{:?}", err);
+    }
+
+    #[test]
+    fn sources_can_be_used_as_trait_objects() {
+        let source: Box<dyn ConfigSource> = Box::new(StaticConfigSource::new().with("app", "G", r#"{"port": 4}"#));
+        let config: Config = block_on(crate::from_source(source.as_ref(), "app", "G")).unwrap();
+        assert_eq!(config.port, 4);
+    }
+}
@@ -0,0 +1,55 @@
+//! Custom CA and client certificates for the HTTP connections to Nacos
+//!
+//! `NACOS_CA_CERT` names a PEM bundle trusted in addition to the system roots, and
+//! `NACOS_CLIENT_CERT` with `NACOS_CLIENT_KEY` a PEM certificate and PKCS#8 key for mTLS. The
+//! SDK's gRPC connection offers no way to configure them, so they require the HTTP protocol.
+
+use crate::{NacosConfig, NacosError};
+
+/// Whether any custom certificate is configured
+pub(crate) fn is_configured(cfg: &NacosConfig) -> bool {
+    cfg.ca_cert.is_some() || cfg.client_cert.is_some() || cfg.client_key.is_some()
+}
+
+/// An HTTP client trusting the configured CA and presenting the configured client certificate
+#[cfg(feature = "tls")]
+pub(crate) fn http_client(cfg: &NacosConfig) -> Result<reqwest::Client, NacosError> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(path) = &cfg.ca_cert {
+        let certs = reqwest::Certificate::from_pem_bundle(&read(path, "NACOS_CA_CERT")?)
+            .map_err(|e| NacosError::NacosConnectionError(format!("Failed to parse the CA certificates in NACOS_CA_CERT: {}: {}", path, e), Some(e.into())))?;
+        if certs.is_empty() {
+            return Err(NacosError::NacosConnectionError(format!("No CA certificate in NACOS_CA_CERT: {}", path), None));
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    match (&cfg.client_cert, &cfg.client_key) {
+        (Some(cert), Some(key)) => {
+            let identity = reqwest::Identity::from_pkcs8_pem(&read(cert, "NACOS_CLIENT_CERT")?, &read(key, "NACOS_CLIENT_KEY")?)
+                .map_err(|e| NacosError::NacosConnectionError(format!("Failed to parse the client certificate and key: {}, {}: {}", cert, key, e), Some(e.into())))?;
+            builder = builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(NacosError::NacosConnectionError("NACOS_CLIENT_CERT and NACOS_CLIENT_KEY must be set together".to_string(), None));
+        }
+    }
+    builder
+        .build()
+        .map_err(|e| NacosError::NacosConnectionError(format!("Failed to create the HTTP client for nacos: {}", e), Some(e.into())))
+}
+
+#[cfg(not(feature = "tls"))]
+pub(crate) fn http_client(cfg: &NacosConfig) -> Result<reqwest::Client, NacosError> {
+    if is_configured(cfg) {
+        return Err(NacosError::NacosConnectionError("Custom TLS certificates require the `tls` feature".to_string(), None));
+    }
+    Ok(reqwest::Client::new())
+}
+
+#[cfg(feature = "tls")]
+fn read(path: &str, name: &str) -> Result<Vec<u8>, NacosError> {
+    std::fs::read(path).map_err(|e| NacosError::NacosConnectionError(format!("Failed to read {}: {}: {}", name, path, e), Some(e.into())))
+}
@@ -0,0 +1,17 @@
+//! Logging macros forwarding to `tracing` when the `tracing` feature is enabled
+//!
+//! Without the feature the macros expand to nothing, so logging is zero-cost.
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+    };
+}
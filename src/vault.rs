@@ -0,0 +1,97 @@
+//! HashiCorp Vault transit decryption of `ENC(vault:v1:...)` passwords
+
+use crate::{NacosError, SecretDecryptor};
+use base64::Engine;
+use serde_json::{json, Value};
+use std::{env, fmt};
+
+/// Decrypts `ENC(...)` passwords with a key of the Vault transit secrets engine
+///
+/// `Debug` hides the token.
+#[derive(Clone)]
+pub struct VaultDecryptor {
+    /// Vault address, e.g. `https://vault.example.com:8200`
+    pub addr: String,
+    /// Token allowed to use the key for decryption
+    pub token: String,
+    /// Name of the transit key
+    pub key: String,
+    /// Mount path of the transit engine, `transit` by default
+    pub mount: String,
+}
+
+impl fmt::Debug for VaultDecryptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultDecryptor")
+            .field("addr", &self.addr)
+            .field("token", &"***")
+            .field("key", &self.key)
+            .field("mount", &self.mount)
+            .finish()
+    }
+}
+
+impl VaultDecryptor {
+    /// Use the transit `key` mounted at `transit`
+    pub fn new(addr: &str, token: &str, key: &str) -> Self {
+        VaultDecryptor {
+            addr: addr.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            key: key.to_string(),
+            mount: "transit".to_string(),
+        }
+    }
+
+    /// Read `VAULT_ADDR`, `VAULT_TOKEN`, `VAULT_TRANSIT_KEY` and the optional `VAULT_TRANSIT_MOUNT`
+    pub fn from_env() -> Result<Self, NacosError> {
+        let var = |name: &str| {
+            env::var(name).map_err(|e| NacosError::EnvVarError(format!("{} not set", name), Some(e.into())))
+        };
+        let mut decryptor = VaultDecryptor::new(&var("VAULT_ADDR")?, &var("VAULT_TOKEN")?, &var("VAULT_TRANSIT_KEY")?);
+        if let Ok(mount) = env::var("VAULT_TRANSIT_MOUNT") {
+            decryptor.mount = mount.trim_matches('/').to_string();
+        }
+        Ok(decryptor)
+    }
+}
+
+#[async_trait::async_trait]
+impl SecretDecryptor for VaultDecryptor {
+    /// Decrypt Vault transit ciphertext, e.g. `vault:v1:...`
+    async fn decrypt(&self, ciphertext: &str) -> Result<String, NacosError> {
+        let url = format!("{}/v1/{}/decrypt/{}", self.addr, self.mount, self.key);
+        let resp: Value = reqwest::Client::new()
+            .post(&url)
+            .header("X-Vault-Token", &self.token)
+            .json(&json!({ "ciphertext": ciphertext }))
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|e| NacosError::VaultError(format!("Failed to decrypt with vault: {}: {}", url, e), Some(e.into())))?
+            .json()
+            .await
+            .map_err(|e| NacosError::VaultError(format!("Failed to read vault's response: {}", e), Some(e.into())))?;
+
+        let plaintext = resp["data"]["plaintext"]
+            .as_str()
+            .ok_or_else(|| NacosError::VaultError("Failed to get plaintext from vault's response".to_string(), None))?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(plaintext)
+            .map_err(|e| NacosError::Base64DecodeError(format!("Failed to decode base64 plaintext from vault: {}", e), Some(e.into())))?;
+        Ok(String::from_utf8(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_hides_the_token() {
+        let decryptor = VaultDecryptor::new("https://vault.example.com:8200/", "s.hunter2", "app");
+        let debug = format!("{:?}", decryptor);
+        assert!(!debug.contains("hunter2"), "{}", debug);
+        assert!(debug.contains(r#"token: "***""#), "{}", debug);
+        assert!(debug.contains("https://vault.example.com:8200\""), "{}", debug);
+    }
+}